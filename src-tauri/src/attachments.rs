@@ -0,0 +1,184 @@
+use base64::Engine;
+use rusqlite::{Connection, Result as SqliteResult};
+use crate::database::{attachment_from_row, ATTACHMENT_COLUMNS};
+use crate::documents;
+use crate::models::{Attachment, ContentPart, ImageUrl};
+
+pub const MAX_IMAGE_SIZE: usize = 10 * 1024 * 1024;
+
+pub struct AttachedDocument {
+    pub id: i64,
+    pub file_name: String,
+    pub text: String,
+}
+
+/// Detects the image type from its first bytes instead of trusting the
+/// file extension. Only formats accepted by the common vision models.
+pub fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Stores a file that is not linked to a message yet. It gets linked when
+/// the message is sent, or discarded by `discard_stale`. Images are kept as
+/// they are; any other file must be a document we can extract text from.
+pub fn store(conn: &Connection, file_name: &str, bytes: &[u8]) -> Result<Attachment, String> {
+    let Some(mime_type) = sniff_image_mime(bytes) else {
+        return store_document(conn, file_name, bytes);
+    };
+    
+    if bytes.len() > MAX_IMAGE_SIZE {
+        return Err(format!(
+            "Imagem muito grande ({} MB, máximo {} MB)",
+            bytes.len().div_ceil(1024 * 1024),
+            MAX_IMAGE_SIZE / (1024 * 1024)
+        ));
+    }
+    
+    conn.execute(
+        "INSERT INTO attachments (kind, file_name, mime_type, size, data) VALUES ('image', ?1, ?2, ?3, ?4)",
+        rusqlite::params![file_name, mime_type, bytes.len() as i64, bytes],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    load(conn, conn.last_insert_rowid()).map_err(|e| e.to_string())
+}
+
+fn store_document(conn: &Connection, file_name: &str, bytes: &[u8]) -> Result<Attachment, String> {
+    let document = documents::extract_text(file_name, bytes)?;
+    
+    conn.execute(
+        "INSERT INTO attachments (kind, file_name, mime_type, size, data, text) VALUES ('document', ?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![file_name, document.mime_type, bytes.len() as i64, bytes, document.text],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    load(conn, conn.last_insert_rowid()).map_err(|e| e.to_string())
+}
+
+pub fn load(conn: &Connection, attachment_id: i64) -> SqliteResult<Attachment> {
+    conn.query_row(
+        &format!("SELECT {} FROM attachments WHERE id = ?1", ATTACHMENT_COLUMNS),
+        [attachment_id],
+        attachment_from_row,
+    )
+}
+
+/// Links pending attachments to the message they were sent with.
+pub fn link_pending(conn: &Connection, attachment_ids: &[i64], message_id: i64) -> Result<(), String> {
+    for attachment_id in attachment_ids {
+        let linked = conn
+            .execute(
+                "UPDATE attachments SET message_id = ?1 WHERE id = ?2 AND message_id IS NULL",
+                [message_id, *attachment_id],
+            )
+            .map_err(|e| e.to_string())?;
+        
+        if linked == 0 {
+            return Err(format!("Anexo {} não encontrado ou já enviado", attachment_id));
+        }
+    }
+    
+    Ok(())
+}
+
+/// Gives an edited copy of a message the same attachments as the original.
+pub fn copy_to(conn: &Connection, from_message_id: i64, to_message_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO attachments (message_id, kind, file_name, mime_type, size, data, text, created_at)
+         SELECT ?2, kind, file_name, mime_type, size, data, text, created_at
+         FROM attachments WHERE message_id = ?1 ORDER BY id",
+        [from_message_id, to_message_id],
+    )?;
+    
+    Ok(())
+}
+
+pub fn for_conversation(conn: &Connection, conversation_id: i64) -> SqliteResult<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attachments
+         WHERE message_id IN (SELECT id FROM messages WHERE conversation_id = ?1)
+         ORDER BY id ASC",
+        ATTACHMENT_COLUMNS
+    ))?;
+    
+    let attachments = stmt
+        .query_map([conversation_id], attachment_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(attachments)
+}
+
+pub fn data_uri(conn: &Connection, attachment_id: i64) -> SqliteResult<String> {
+    let (mime_type, data): (String, Vec<u8>) = conn.query_row(
+        "SELECT mime_type, data FROM attachments WHERE id = ?1",
+        [attachment_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    
+    Ok(format!(
+        "data:{};base64,{}",
+        mime_type,
+        base64::engine::general_purpose::STANDARD.encode(data)
+    ))
+}
+
+/// Image parts for a message of the history, in the order they were attached.
+pub fn image_parts(conn: &Connection, message_id: i64) -> SqliteResult<Vec<ContentPart>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM attachments WHERE message_id = ?1 AND kind = 'image' ORDER BY id ASC",
+    )?;
+    
+    let ids = stmt
+        .query_map([message_id], |row| row.get::<_, i64>(0))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    ids.into_iter()
+        .map(|id| {
+            Ok(ContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url: data_uri(conn, id)?,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Extracted text of the documents attached to a message.
+pub fn documents(conn: &Connection, message_id: i64) -> SqliteResult<Vec<AttachedDocument>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, file_name, text FROM attachments
+         WHERE message_id = ?1 AND kind = 'document' ORDER BY id ASC",
+    )?;
+    
+    let documents = stmt
+        .query_map([message_id], |row| {
+            Ok(AttachedDocument {
+                id: row.get(0)?,
+                file_name: row.get(1)?,
+                text: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(documents)
+}
+
+/// Drops attachments that were uploaded but never sent.
+pub fn discard_stale(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "DELETE FROM attachments WHERE message_id IS NULL AND created_at < datetime('now', '-1 day')",
+        [],
+    )?;
+    
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult};
+use crate::database::{message_from_row, MESSAGE_COLUMNS};
+use crate::models::Message;
+
+/// Messages form a tree through `parent_message_id`; the conversation's
+/// `current_message_id` points at the leaf of the branch being shown.
+pub fn active_branch(conn: &Connection, conversation_id: i64) -> SqliteResult<Vec<Message>> {
+    let current: Option<i64> = conn
+        .query_row(
+            "SELECT current_message_id FROM conversations WHERE id = ?1",
+            [conversation_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    
+    match current {
+        Some(message_id) => branch_to(conn, message_id),
+        None => Ok(vec![]),
+    }
+}
+
+/// Returns the path from the root of the tree down to `message_id`.
+pub fn branch_to(conn: &Connection, message_id: i64) -> SqliteResult<Vec<Message>> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH RECURSIVE branch(message_id, depth) AS (
+            SELECT ?1, 0
+            UNION ALL
+            SELECT m.parent_message_id, b.depth + 1
+            FROM messages m JOIN branch b ON m.id = b.message_id
+            WHERE m.parent_message_id IS NOT NULL
+        )
+        SELECT {} FROM messages JOIN branch ON messages.id = branch.message_id
+        ORDER BY branch.depth DESC",
+        MESSAGE_COLUMNS
+    ))?;
+    
+    let messages = stmt
+        .query_map([message_id], message_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(messages)
+}
+
+/// Follows the newest child at every level until reaching a leaf.
+pub fn newest_leaf(conn: &Connection, message_id: i64) -> SqliteResult<i64> {
+    let mut leaf = message_id;
+    
+    while let Some(child) = conn
+        .query_row(
+            "SELECT id FROM messages WHERE parent_message_id = ?1 ORDER BY id DESC LIMIT 1",
+            [leaf],
+            |row| row.get(0),
+        )
+        .optional()?
+    {
+        leaf = child;
+    }
+    
+    Ok(leaf)
+}
+
+pub fn siblings(conn: &Connection, message: &Message) -> SqliteResult<Vec<Message>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM messages
+         WHERE conversation_id = ?1 AND role = ?2 AND parent_message_id IS ?3
+         ORDER BY id ASC",
+        MESSAGE_COLUMNS
+    ))?;
+    
+    let messages = stmt
+        .query_map(
+            rusqlite::params![message.conversation_id, message.role, message.parent_message_id],
+            message_from_row,
+        )?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(messages)
+}
+
+pub fn current_message_id(conn: &Connection, conversation_id: i64) -> SqliteResult<Option<i64>> {
+    conn.query_row(
+        "SELECT current_message_id FROM conversations WHERE id = ?1",
+        [conversation_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn set_current(conn: &Connection, conversation_id: i64, message_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE conversations SET current_message_id = ?1, updated_at = datetime('now') WHERE id = ?2",
+        [message_id, conversation_id],
+    )?;
+    
+    Ok(())
+}
+
+pub struct NewMessage<'a> {
+    pub conversation_id: i64,
+    pub parent_message_id: Option<i64>,
+    pub role: &'a str,
+    pub content: &'a str,
+    pub stopped: bool,
+    pub model: Option<&'a str>,
+}
+
+/// Inserts a message under its parent and makes it the current leaf.
+pub fn insert_message(conn: &Connection, message: NewMessage) -> SqliteResult<i64> {
+    conn.execute(
+        "INSERT INTO messages (conversation_id, parent_message_id, role, content, stopped, model)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            message.conversation_id,
+            message.parent_message_id,
+            message.role,
+            message.content,
+            message.stopped,
+            message.model,
+        ],
+    )?;
+    
+    let id = conn.last_insert_rowid();
+    set_current(conn, message.conversation_id, id)?;
+    
+    Ok(id)
+}
+
+pub fn load_message(conn: &Connection, message_id: i64) -> SqliteResult<Message> {
+    conn.query_row(
+        &format!("SELECT {} FROM messages WHERE id = ?1", MESSAGE_COLUMNS),
+        [message_id],
+        message_from_row,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    
+    fn add(conn: &Connection, parent_message_id: Option<i64>, role: &str, content: &str) -> i64 {
+        insert_message(
+            conn,
+            NewMessage {
+                conversation_id: 1,
+                parent_message_id,
+                role,
+                content,
+                stopped: false,
+                model: None,
+            },
+        )
+        .unwrap()
+    }
+    
+    fn ids(messages: Vec<Message>) -> Vec<i64> {
+        messages.into_iter().map(|m| m.id).collect()
+    }
+    
+    fn select(conn: &Connection, message_id: i64) -> Vec<i64> {
+        let leaf = newest_leaf(conn, message_id).unwrap();
+        set_current(conn, 1, leaf).unwrap();
+        ids(active_branch(conn, 1).unwrap())
+    }
+    
+    fn conversation() -> Database {
+        let db = Database::in_memory();
+        db.conn
+            .lock()
+            .unwrap()
+            .execute("INSERT INTO conversations (id, title) VALUES (1, 'Ramos')", [])
+            .unwrap();
+        db
+    }
+    
+    #[test]
+    fn switching_to_a_sibling_and_back_restores_each_branch() {
+        let db = conversation();
+        let conn = db.conn.lock().unwrap();
+        let question = add(&conn, None, "user", "Oi");
+        let first = add(&conn, Some(question), "assistant", "Olá");
+        let follow_up = add(&conn, Some(first), "user", "Tudo bem?");
+        let second = add(&conn, Some(question), "assistant", "Oi!");
+        
+        assert_eq!(ids(siblings(&conn, &load_message(&conn, first).unwrap()).unwrap()), vec![first, second]);
+        assert_eq!(ids(active_branch(&conn, 1).unwrap()), vec![question, second]);
+        assert_eq!(select(&conn, first), vec![question, first, follow_up]);
+        assert_eq!(select(&conn, second), vec![question, second]);
+        assert_eq!(select(&conn, first), vec![question, first, follow_up]);
+        assert_eq!(current_message_id(&conn, 1).unwrap(), Some(follow_up));
+    }
+    
+    #[test]
+    fn newest_leaf_follows_the_newest_child_down_a_deep_branch() {
+        let db = conversation();
+        let conn = db.conn.lock().unwrap();
+        let root = add(&conn, None, "user", "0");
+        
+        let mut parent = root;
+        for depth in 1..50 {
+            parent = add(&conn, Some(parent), if depth % 2 == 1 { "assistant" } else { "user" }, "…");
+        }
+        let old_side = add(&conn, Some(root), "assistant", "antigo");
+        let deep_leaf = parent;
+        
+        assert_eq!(newest_leaf(&conn, root).unwrap(), old_side);
+        assert_eq!(newest_leaf(&conn, root + 1).unwrap(), deep_leaf);
+        assert_eq!(newest_leaf(&conn, deep_leaf).unwrap(), deep_leaf);
+        assert_eq!(branch_to(&conn, deep_leaf).unwrap().len(), 50);
+    }
+    
+    #[test]
+    fn editing_forks_the_branch_at_the_edited_message() {
+        let db = conversation();
+        let conn = db.conn.lock().unwrap();
+        let question = add(&conn, None, "user", "Quanto é 2 + 2?");
+        let answer = add(&conn, Some(question), "assistant", "4");
+        let follow_up = add(&conn, Some(answer), "user", "E 3 + 3?");
+        add(&conn, Some(follow_up), "assistant", "6");
+        
+        let edited = add(&conn, Some(answer), "user", "E 4 + 4?");
+        let reply = add(&conn, Some(edited), "assistant", "8");
+        
+        let branch = active_branch(&conn, 1).unwrap();
+        assert_eq!(ids(branch.clone()), vec![question, answer, edited, reply]);
+        assert_eq!(branch[2].content, "E 4 + 4?");
+        assert_eq!(ids(siblings(&conn, &branch[2]).unwrap()), vec![follow_up, edited]);
+    }
+    
+    #[test]
+    fn conversation_without_messages_has_an_empty_branch() {
+        let db = conversation();
+        let conn = db.conn.lock().unwrap();
+        
+        assert!(active_branch(&conn, 1).unwrap().is_empty());
+        assert!(active_branch(&conn, 42).unwrap().is_empty());
+    }
+}
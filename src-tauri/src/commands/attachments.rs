@@ -0,0 +1,56 @@
+use std::path::Path;
+use tauri::State;
+use crate::attachments;
+use crate::database::Database;
+use crate::documents;
+use crate::models::Attachment;
+
+/// Reads an image or a document from disk and keeps it pending until the
+/// message it belongs to is sent with its id in `attachment_ids`.
+#[tauri::command]
+pub fn add_attachment(db: State<Database>, path: String) -> Result<Attachment, String> {
+    let path = Path::new(&path);
+    
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Erro ao ler anexo: {}", e))?
+        .len();
+    if size > documents::MAX_DOCUMENT_SIZE as u64 {
+        return Err(format!(
+            "Arquivo muito grande (máximo {} MB)",
+            documents::MAX_DOCUMENT_SIZE / (1024 * 1024)
+        ));
+    }
+    
+    let bytes = std::fs::read(path).map_err(|e| format!("Erro ao ler anexo: {}", e))?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "anexo".to_string());
+    
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    attachments::store(&conn, &file_name, &bytes)
+}
+
+/// Removes an attachment that has not been sent yet.
+#[tauri::command]
+pub fn delete_attachment(db: State<Database>, id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    conn.execute("DELETE FROM attachments WHERE id = ?1 AND message_id IS NULL", [id])
+        .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_attachments(db: State<Database>, conversation_id: i64) -> Result<Vec<Attachment>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    attachments::for_conversation(&conn, conversation_id).map_err(|e| e.to_string())
+}
+
+/// Returns the attachment as a `data:` URI, ready for an `<img>` tag.
+#[tauri::command]
+pub fn get_attachment_data(db: State<Database>, id: i64) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    attachments::data_uri(&conn, id).map_err(|e| e.to_string())
+}
@@ -1,25 +1,104 @@
-use tauri::State;
-use crate::database::Database;
-use crate::models::{ChatMessage, ChatRequest, ChatResponse, Conversation, Message};
+use rusqlite::Connection;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::attachments::{self, AttachedDocument};
+use crate::branches::{self, NewMessage};
+use crate::context::{self, SUMMARY_RESERVE};
+use crate::database::{conversation_from_row, parse_generation_params, Database, CONVERSATION_COLUMNS};
+use crate::commands::conversations::rename_conversation;
+use crate::commands::knowledge::{knowledge_endpoint, load_knowledge_base};
+use crate::commands::ollama::embed;
+use crate::commands::providers::{resolve_endpoint, Endpoint};
+use crate::generations::{CancelAction, GenerationGuard, Generations};
+use crate::knowledge;
+use crate::tools::{self, ToolApprovals, ToolRegistry};
+use crate::secrets::SecretStore;
+use crate::models::{
+    ChatDeltaEvent, ChatDoneEvent, ChatMessage, ChatMessageResponse, ChatRequest, ChatResponse,
+    ChatStreamChunk, Citation, ContentPart, ContextReport, ContextStrategy, Conversation,
+    ConversationTitleEvent, GenerationParams, KnowledgeBase, Message, MessageContent, ToolApprovalRequest,
+    ToolCall, ToolCallEvent, ToolDefinition,
+};
 
 #[tauri::command]
 pub async fn chat_completion(
+    db: State<'_, Database>,
+    messages: Vec<ChatMessage>,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+    api_key: Option<String>,
+    model: String,
+    params: Option<GenerationParams>,
+) -> Result<String, String> {
+    let endpoint = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        resolve_endpoint(&conn, db.secrets.as_ref(), provider_id, api_url, api_key)?
+    };
+    
+    request_chat_completion(
+        messages,
+        endpoint.api_url,
+        endpoint.api_key,
+        model,
+        params.unwrap_or_default(),
+    )
+    .await
+}
+
+pub async fn request_chat_completion(
     messages: Vec<ChatMessage>,
     api_url: String,
     api_key: String,
     model: String,
+    params: GenerationParams,
 ) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    
-    let url = format!("{}/chat/completions", api_url.trim_end_matches('/'));
+    let message = request_chat_message(messages, api_url, api_key, model, params, vec![]).await?;
     
+    Ok(message.content.unwrap_or_else(|| "Sem resposta".to_string()))
+}
+
+/// Non-streaming request that returns the whole assistant message, tool
+/// calls included.
+pub async fn request_chat_message(
+    messages: Vec<ChatMessage>,
+    api_url: String,
+    api_key: String,
+    model: String,
+    params: GenerationParams,
+    tools: Vec<ToolDefinition>,
+) -> Result<ChatMessageResponse, String> {
     let request_body = ChatRequest {
         model,
         messages,
         stream: false,
+        params,
+        tools,
     };
     
-    let mut request = client.post(&url).json(&request_body);
+    let response = post_chat_request(&api_url, &api_key, &request_body).await?;
+    
+    let chat_response: ChatResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Erro ao parsear resposta: {}", e))?;
+    
+    chat_response
+        .choices
+        .into_iter()
+        .next()
+        .map(|c| c.message)
+        .ok_or_else(|| "Sem resposta".to_string())
+}
+
+async fn post_chat_request(
+    api_url: &str,
+    api_key: &str,
+    request_body: &ChatRequest,
+) -> Result<reqwest::Response, String> {
+    let client = reqwest::Client::new();
+    
+    let url = format!("{}/chat/completions", api_url.trim_end_matches('/'));
+    
+    let mut request = client.post(&url).json(request_body);
     
     if !api_key.is_empty() {
         request = request.header("Authorization", format!("Bearer {}", api_key));
@@ -36,138 +115,1328 @@ pub async fn chat_completion(
         return Err(format!("API retornou erro {}: {}", status, text));
     }
     
-    let chat_response: ChatResponse = response
-        .json()
+    Ok(response)
+}
+
+/// Streams an OpenAI-compatible chat completion, calling `on_delta` for every
+/// content fragment and returning the full reply once the stream ends.
+pub async fn chat_completion_stream<F>(
+    messages: Vec<ChatMessage>,
+    api_url: String,
+    api_key: String,
+    model: String,
+    params: GenerationParams,
+    mut on_delta: F,
+) -> Result<String, String>
+where
+    F: FnMut(&str),
+{
+    let request_body = ChatRequest {
+        model,
+        messages,
+        stream: true,
+        params,
+        tools: vec![],
+    };
+    
+    let mut response = post_chat_request(&api_url, &api_key, &request_body).await?;
+    
+    let mut content = String::new();
+    let mut buffer: Vec<u8> = Vec::new();
+    
+    'stream: while let Some(chunk) = response
+        .chunk()
         .await
-        .map_err(|e| format!("Erro ao parsear resposta: {}", e))?;
+        .map_err(|e| format!("Erro ao ler stream: {}", e))?
+    {
+        buffer.extend_from_slice(&chunk);
+        
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            
+            match parse_sse_line(line.trim())? {
+                SseLine::Delta(delta) => {
+                    on_delta(&delta);
+                    content.push_str(&delta);
+                }
+                SseLine::Done => break 'stream,
+                SseLine::Skip => {}
+            }
+        }
+    }
     
-    let content = chat_response
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .unwrap_or_else(|| "Sem resposta".to_string());
+    if content.is_empty() {
+        content = "Sem resposta".to_string();
+    }
     
     Ok(content)
 }
 
-#[tauri::command]
-pub async fn send_message_complete(
-    db: State<'_, Database>,
+const GENERATION_CANCELLED: &str = "Geração cancelada";
+
+enum SseLine {
+    Delta(String),
+    Done,
+    Skip,
+}
+
+fn parse_sse_line(line: &str) -> Result<SseLine, String> {
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(SseLine::Skip);
+    };
+    
+    let data = data.trim();
+    if data == "[DONE]" {
+        return Ok(SseLine::Done);
+    }
+    
+    let chunk: ChatStreamChunk = serde_json::from_str(data)
+        .map_err(|e| format!("Erro ao parsear chunk do stream: {}", e))?;
+    
+    let delta = chunk
+        .choices
+        .into_iter()
+        .filter_map(|c| c.delta.content)
+        .collect::<String>();
+    
+    if delta.is_empty() {
+        Ok(SseLine::Skip)
+    } else {
+        Ok(SseLine::Delta(delta))
+    }
+}
+
+struct Turn {
+    conv_id: i64,
+    user_msg_id: i64,
+    messages_for_api: Vec<ChatMessage>,
+    params: GenerationParams,
+    api_url: String,
+    api_key: String,
+    model: String,
+    context: ContextReport,
+    summary: Option<PendingSummary>,
+    retrieval: Option<PendingRetrieval>,
+    /// Only honoured by the non-streaming path, which can run tool calls.
+    tools_enabled: bool,
+    /// Fallback title of a conversation created by this turn, to be replaced
+    /// by one suggested by the model.
+    pending_title: Option<String>,
+}
+
+/// Knowledge bases attached to the conversation, searched with the user
+/// message right before the request is sent.
+struct PendingRetrieval {
+    query: String,
+    sources: Vec<(KnowledgeBase, Endpoint)>,
+    budget: usize,
+}
+
+/// Turns dropped from the history that still have to be folded into the
+/// conversation summary before the request is sent.
+struct PendingSummary {
+    previous: Option<String>,
+    messages: Vec<ChatMessage>,
+    through: i64,
+    insert_at: usize,
+    budget: usize,
+}
+
+const SUMMARY_PROMPT: &str = "Resuma a conversa a seguir em poucas frases, mantendo fatos, decisões e pedidos do usuário que ainda possam ser relevantes. Responda apenas com o resumo.";
+
+fn merge_params(overrides: Option<GenerationParams>, defaults: GenerationParams) -> GenerationParams {
+    let Some(overrides) = overrides else {
+        return defaults;
+    };
+    
+    GenerationParams {
+        temperature: overrides.temperature.or(defaults.temperature),
+        top_p: overrides.top_p.or(defaults.top_p),
+        max_tokens: overrides.max_tokens.or(defaults.max_tokens),
+        stop: overrides.stop.or(defaults.stop),
+        seed: overrides.seed.or(defaults.seed),
+    }
+}
+
+/// Endpoint and model requested by the caller; a conversation that is
+/// already bound to a model and endpoint ignores them.
+#[derive(Default)]
+struct TurnOptions {
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+    api_key: Option<String>,
+    model: Option<String>,
+}
+
+fn begin_turn(
+    db: &Database,
     conversation_id: Option<i64>,
-    user_input: String,
+    user_input: &str,
+    attachment_ids: &[i64],
+    options: TurnOptions,
+) -> Result<Turn, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    let mut pending_title = None;
+    
+    let conv_id = if let Some(id) = conversation_id {
+        id
+    } else {
+        let title = fallback_title(user_input);
+        
+        tx.execute(
+            "INSERT INTO conversations (title) VALUES (?1)",
+            rusqlite::params![title],
+        )
+        .map_err(|e| e.to_string())?;
+        let id = tx.last_insert_rowid();
+        
+        let auto_title: bool = tx
+            .query_row("SELECT auto_title FROM settings WHERE id = 1", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if auto_title {
+            pending_title = Some(title);
+        }
+        
+        id
+    };
+    
+    let parent_message_id = branches::current_message_id(&tx, conv_id).map_err(|e| e.to_string())?;
+    
+    let user_msg_id = branches::insert_message(
+        &tx,
+        NewMessage {
+            conversation_id: conv_id,
+            parent_message_id,
+            role: "user",
+            content: user_input,
+            stopped: false,
+            model: None,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    
+    attachments::link_pending(&tx, attachment_ids, user_msg_id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    let mut turn = prepare_turn(&conn, db.secrets.as_ref(), conv_id, user_msg_id, options)?;
+    turn.pending_title = pending_title;
+    
+    Ok(turn)
+}
+
+const TITLE_MAX_CHARS: usize = 50;
+
+const TITLE_PROMPT: &str = "Crie um título curto, de no máximo seis palavras, para a conversa abaixo. Responda apenas com o título, sem aspas e sem pontuação final.";
+
+/// Title used until the model suggests a better one: the first line of the
+/// message, cut on a character boundary.
+fn fallback_title(user_input: &str) -> String {
+    let line = user_input.trim().lines().next().unwrap_or_default().trim();
+    
+    if line.is_empty() {
+        "Nova conversa".to_string()
+    } else if line.chars().count() > TITLE_MAX_CHARS {
+        let cut: String = line.chars().take(TITLE_MAX_CHARS).collect();
+        format!("{}...", cut.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+const TITLE_QUOTES: [char; 8] = ['"', '\'', '*', '`', '“', '”', '«', '»'];
+
+fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let title = line
+        .trim_start_matches(['#', '*'])
+        .trim_start()
+        .trim_start_matches("Título:")
+        .trim()
+        .trim_start_matches(TITLE_QUOTES)
+        .trim_end_matches(|c| c == '.' || TITLE_QUOTES.contains(&c))
+        .trim();
+    
+    if title.is_empty() {
+        return None;
+    }
+    
+    Some(title.chars().take(TITLE_MAX_CHARS * 2).collect())
+}
+
+/// Endpoint and fallback title needed to name a new conversation once its
+/// first reply arrives.
+struct TitleJob {
+    conv_id: i64,
+    fallback: String,
     api_url: String,
     api_key: String,
     model: String,
-) -> Result<(Conversation, Message, Message), String> {
-    let (conv_id, user_msg_id, messages_for_api) = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+}
+
+fn title_job(turn: &mut Turn) -> Option<TitleJob> {
+    turn.pending_title.take().map(|fallback| TitleJob {
+        conv_id: turn.conv_id,
+        fallback,
+        api_url: turn.api_url.clone(),
+        api_key: turn.api_key.clone(),
+        model: turn.model.clone(),
+    })
+}
+
+/// Asks the conversation's model for a title in the background. The
+/// fallback title is only replaced if the user has not renamed it meanwhile.
+fn spawn_title_job(app: AppHandle, job: TitleJob, question: String, answer: String) {
+    tauri::async_runtime::spawn(async move {
+        let excerpt = |text: &str| text.chars().take(1000).collect::<String>();
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: TITLE_PROMPT.to_string().into(),
+                ..Default::default()
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: format!("Usuário: {}\n\nAssistente: {}", excerpt(&question), excerpt(&answer)).into(),
+                ..Default::default()
+            },
+        ];
+        let params = GenerationParams {
+            max_tokens: Some(32),
+            ..Default::default()
+        };
+        
+        let Ok(raw) = request_chat_completion(messages, job.api_url, job.api_key, job.model, params).await else {
+            return;
+        };
+        let Some(title) = clean_title(&raw) else {
+            return;
+        };
+        
+        {
+            let db = app.state::<Database>();
+            let Ok(conn) = db.conn.lock() else {
+                return;
+            };
+            
+            let current: Option<String> = conn
+                .query_row("SELECT title FROM conversations WHERE id = ?1", [job.conv_id], |row| row.get(0))
+                .ok();
+            if current.as_deref() != Some(job.fallback.as_str()) {
+                return;
+            }
+            
+            if rename_conversation(&conn, job.conv_id, &title).is_err() {
+                return;
+            }
+        }
         
-        let conv_id = if let Some(id) = conversation_id {
-            id
-        } else {
-            let title = if user_input.len() > 50 {
-                format!("{}...", &user_input[..50])
+        let _ = app.emit(
+            "conversation-title-updated",
+            ConversationTitleEvent {
+                conversation_id: job.conv_id,
+                title,
+            },
+        );
+    });
+}
+
+/// Builds the request for a reply to `user_msg_id`, using the branch that
+/// leads to it as history.
+fn prepare_turn(
+    conn: &Connection,
+    secrets: &dyn SecretStore,
+    conv_id: i64,
+    user_msg_id: i64,
+    options: TurnOptions,
+) -> Result<Turn, String> {
+    let history = branches::branch_to(conn, user_msg_id).map_err(|e| e.to_string())?;
+    let query = history.last().map(|message| message.content.clone()).unwrap_or_default();
+    let history_ids: Vec<i64> = history.iter().map(|message| message.id).collect();
+    let documents = history_ids
+        .iter()
+        .map(|id| attachments::documents(conn, *id))
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    let mut messages_for_api: Vec<ChatMessage> = history
+        .into_iter()
+        .map(|message| {
+            let images = attachments::image_parts(conn, message.id)?;
+            let content = if images.is_empty() {
+                MessageContent::Text(message.content)
             } else {
-                user_input.clone()
+                let mut parts = vec![ContentPart::Text { text: message.content }];
+                parts.extend(images);
+                MessageContent::Parts(parts)
             };
             
-            conn.execute(
-                "INSERT INTO conversations (title) VALUES (?1)",
-                rusqlite::params![title],
+            Ok(ChatMessage {
+                role: message.role,
+                content,
+                ..Default::default()
+            })
+        })
+        .collect::<rusqlite::Result<_>>()
+        .map_err(|e| e.to_string())?;
+    
+    let (default_model, default_params, default_system_prompt): (String, GenerationParams, String) = conn
+        .query_row(
+            "SELECT model, generation_params, system_prompt FROM settings WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, parse_generation_params(row.get(1)?), row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let conversation = conn
+        .query_row(
+            &format!("SELECT {} FROM conversations WHERE id = ?1", CONVERSATION_COLUMNS),
+            [conv_id],
+            conversation_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let bound = conversation.model.is_some()
+        && (conversation.provider_id.is_some() || conversation.api_url.is_some());
+    let model = conversation.model.or(options.model).unwrap_or(default_model);
+    
+    let endpoint = if conversation.provider_id.is_some() {
+        resolve_endpoint(conn, secrets, conversation.provider_id, None, None)?
+    } else if conversation.api_url.is_some() {
+        // The caller's key belongs to the URL it asked for, so it is only sent
+        // when that is the bound URL; otherwise the key of a provider
+        // configured for the bound URL is used.
+        let api_key = options.api_key.filter(|_| options.api_url == conversation.api_url);
+        resolve_endpoint(conn, secrets, None, conversation.api_url, api_key)?
+    } else {
+        resolve_endpoint(conn, secrets, options.provider_id, options.api_url, options.api_key)?
+    };
+    
+    if !bound {
+        conn.execute(
+            "UPDATE conversations SET model = ?1, api_url = ?2, provider_id = ?3 WHERE id = ?4",
+            rusqlite::params![model, endpoint.api_url, endpoint.provider_id, conv_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    let params = merge_params(conversation.generation_params, default_params);
+    
+    let system_prompt = conversation.system_prompt.unwrap_or(default_system_prompt);
+    let system_message = (!system_prompt.trim().is_empty()).then(|| ChatMessage {
+        role: "system".to_string(),
+        content: system_prompt.into(),
+        ..Default::default()
+    });
+    
+    let settings = context::load_settings(conn).map_err(|e| e.to_string())?;
+    let context_length = context::context_length(&settings.limits, settings.context_length, &model);
+    let budget = context::prompt_budget(context_length, params.max_tokens);
+    
+    let mut pinned = system_message.as_ref().map(context::estimate_tokens).unwrap_or(0);
+    if settings.strategy == ContextStrategy::Summarize {
+        pinned += SUMMARY_RESERVE as usize;
+    }
+    
+    let truncated_attachment_ids = inline_documents(&mut messages_for_api, documents, pinned, budget);
+    
+    let dropped = context::overflow(&messages_for_api, pinned, budget);
+    let dropped_messages: Vec<ChatMessage> = messages_for_api.drain(..dropped).collect();
+    let dropped_message_ids = history_ids[..dropped].to_vec();
+    
+    let insert_at = usize::from(system_message.is_some());
+    if let Some(message) = system_message {
+        messages_for_api.insert(0, message);
+    }
+    
+    let mut summarized = false;
+    let mut summary = None;
+    
+    if settings.strategy == ContextStrategy::Summarize && !dropped_message_ids.is_empty() {
+        let (cached, cached_through): (Option<String>, Option<i64>) = conn
+            .query_row(
+                "SELECT context_summary, context_summary_through FROM conversations WHERE id = ?1",
+                [conv_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
             .map_err(|e| e.to_string())?;
+        
+        let through = dropped_message_ids[dropped_message_ids.len() - 1];
+        let covered = cached_through
+            .and_then(|id| dropped_message_ids.iter().position(|&dropped_id| dropped_id == id))
+            .filter(|_| cached.is_some());
+        
+        match covered {
+            Some(position) if position + 1 == dropped_message_ids.len() => {
+                messages_for_api.insert(insert_at, summary_message(cached.unwrap_or_default()));
+                summarized = true;
+            }
+            Some(position) => {
+                summary = Some(PendingSummary {
+                    previous: cached,
+                    messages: dropped_messages[position + 1..].to_vec(),
+                    through,
+                    insert_at,
+                    budget,
+                });
+            }
+            None => {
+                summary = Some(PendingSummary {
+                    previous: None,
+                    messages: dropped_messages,
+                    through,
+                    insert_at,
+                    budget,
+                });
+            }
+        }
+    }
+    
+    let knowledge_base_ids = knowledge::attached_to(conn, conv_id).map_err(|e| e.to_string())?;
+    let retrieval = if knowledge_base_ids.is_empty() || query.trim().is_empty() {
+        None
+    } else {
+        let sources = knowledge_base_ids
+            .into_iter()
+            .map(|id| {
+                let knowledge_base = load_knowledge_base(conn, id)?;
+                let endpoint = knowledge_endpoint(conn, secrets, &knowledge_base)?;
+                Ok((knowledge_base, endpoint))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        
+        Some(PendingRetrieval { query, sources, budget })
+    };
+    
+    let context = ContextReport {
+        context_length,
+        estimated_tokens: context::estimate_total(&messages_for_api) as u32,
+        dropped_message_ids,
+        summarized,
+        truncated_attachment_ids,
+        sources: vec![],
+    };
+    
+    Ok(Turn {
+        conv_id,
+        user_msg_id,
+        messages_for_api,
+        params,
+        api_url: endpoint.api_url,
+        api_key: endpoint.api_key,
+        model,
+        context,
+        summary,
+        retrieval,
+        tools_enabled: tools::enabled(conn).map_err(|e| e.to_string())?,
+        pending_title: None,
+    })
+}
+
+/// Shortest cut of a document still worth sending; below this it is left out.
+const MIN_DOCUMENT_TOKENS: usize = 64;
+
+/// Appends the text of attached documents to their messages, newest first,
+/// cutting them to whatever room the history leaves in the budget. Returns
+/// the ids of the documents that were cut or left out.
+fn inline_documents(
+    messages: &mut [ChatMessage],
+    documents: Vec<Vec<AttachedDocument>>,
+    pinned: usize,
+    budget: usize,
+) -> Vec<i64> {
+    let mut available = budget.saturating_sub(pinned + context::estimate_total(messages));
+    let mut truncated = Vec::new();
+    
+    for (message, documents) in messages.iter_mut().zip(documents).rev() {
+        let mut attached = String::new();
+        
+        for document in documents {
+            let (text, cut) = context::truncate_text(&document.text, available.saturating_sub(MIN_DOCUMENT_TOKENS / 4));
+            
+            if cut && available < MIN_DOCUMENT_TOKENS {
+                attached.push_str(&format!(
+                    "\n\n[Arquivo anexado: {} (omitido, não cabe no contexto)]",
+                    document.file_name
+                ));
+                truncated.push(document.id);
+                continue;
+            }
             
-            conn.last_insert_rowid()
+            attached.push_str(&format!("\n\n[Arquivo anexado: {}]\n{}", document.file_name, text));
+            if cut {
+                attached.push_str("\n[... conteúdo truncado para caber no contexto ...]");
+                truncated.push(document.id);
+            }
+            attached.push_str("\n[Fim do arquivo]");
+            
+            available = available.saturating_sub(text.chars().count().div_ceil(4));
+        }
+        
+        if !attached.is_empty() {
+            message.content.push_text(&attached);
+        }
+    }
+    
+    truncated.reverse();
+    truncated
+}
+
+/// Embeds the user message, picks the closest chunks of the attached
+/// knowledge bases and adds them, numbered for citation, after the system
+/// messages. Knowledge bases that cannot be searched are skipped.
+async fn retrieve_context(db: &Database, turn: &mut Turn) {
+    let Some(pending) = turn.retrieval.take() else {
+        return;
+    };
+    
+    let query = [pending.query];
+    let mut scored = Vec::new();
+    
+    for (knowledge_base, endpoint) in &pending.sources {
+        let Ok(mut vectors) = embed(&endpoint.api_url, &endpoint.api_key, &knowledge_base.embedding_model, &query).await else {
+            continue;
+        };
+        let Some(query_vector) = vectors.pop() else {
+            continue;
         };
         
-        conn.execute(
-            "INSERT INTO messages (conversation_id, role, content) VALUES (?1, ?2, ?3)",
-            rusqlite::params![conv_id, "user", user_input],
+        let chunks = match db.conn.lock() {
+            Ok(conn) => knowledge::chunks(&conn, knowledge_base.id).unwrap_or_default(),
+            Err(_) => continue,
+        };
+        
+        for chunk in chunks {
+            let score = knowledge::cosine_similarity(&query_vector, &chunk.embedding);
+            scored.push((score, knowledge_base.id, chunk));
+        }
+    }
+    
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    
+    let mut available = pending
+        .budget
+        .saturating_sub(context::estimate_total(&turn.messages_for_api) + RETRIEVAL_HEADER_TOKENS);
+    let mut excerpts = String::new();
+    
+    for (score, knowledge_base_id, chunk) in scored.into_iter().take(knowledge::TOP_K) {
+        let cost = chunk.content.chars().count().div_ceil(4) + 8;
+        if cost > available {
+            break;
+        }
+        available -= cost;
+        
+        let index = turn.context.sources.len() + 1;
+        excerpts.push_str(&format!("\n\n[{}] {}\n{}", index, chunk.source, chunk.content));
+        turn.context.sources.push(Citation {
+            index,
+            knowledge_base_id,
+            chunk_id: chunk.id,
+            source: chunk.source,
+            score,
+        });
+    }
+    
+    if excerpts.is_empty() {
+        return;
+    }
+    
+    let insert_at = turn
+        .messages_for_api
+        .iter()
+        .take_while(|message| message.role == "system")
+        .count();
+    turn.messages_for_api.insert(
+        insert_at,
+        ChatMessage {
+            role: "system".to_string(),
+            content: format!("{}{}", RETRIEVAL_PROMPT, excerpts).into(),
+            ..Default::default()
+        },
+    );
+    turn.context.estimated_tokens = context::estimate_total(&turn.messages_for_api) as u32;
+}
+
+const RETRIEVAL_PROMPT: &str = "Trechos da base de conhecimento que podem ajudar a responder. Quando usar um trecho, cite a fonte pelo número, como [1].";
+
+/// Rough cost of `RETRIEVAL_PROMPT` and the message around it.
+const RETRIEVAL_HEADER_TOKENS: usize = 40;
+
+fn summary_message(summary: String) -> ChatMessage {
+    ChatMessage {
+        role: "system".to_string(),
+        content: format!("Resumo da conversa até aqui:\n{}", summary).into(),
+        ..Default::default()
+    }
+}
+
+/// Folds the turns dropped from the history into the conversation summary
+/// and adds it to the request. If the summary cannot be generated the turn
+/// goes on with the truncated history.
+async fn summarize_overflow(db: &Database, turn: &mut Turn) {
+    let Some(pending) = turn.summary.take() else {
+        return;
+    };
+    
+    let mut transcript = String::new();
+    if let Some(previous) = &pending.previous {
+        transcript.push_str(&format!("Resumo anterior:\n{}\n\n", previous));
+    }
+    
+    let available = pending
+        .budget
+        .saturating_sub(SUMMARY_RESERVE as usize + transcript.chars().count() / 4);
+    for message in context::newest_within(&pending.messages, available) {
+        transcript.push_str(&format!("{}: {}\n\n", message.role, message.content.text()));
+    }
+    
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: SUMMARY_PROMPT.to_string().into(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: transcript.into(),
+            ..Default::default()
+        },
+    ];
+    let params = GenerationParams {
+        max_tokens: Some(SUMMARY_RESERVE),
+        ..Default::default()
+    };
+    
+    let Ok(summary) = request_chat_completion(
+        messages,
+        turn.api_url.clone(),
+        turn.api_key.clone(),
+        turn.model.clone(),
+        params,
+    )
+    .await
+    else {
+        return;
+    };
+    
+    if let Ok(conn) = db.conn.lock() {
+        let _ = conn.execute(
+            "UPDATE conversations SET context_summary = ?1, context_summary_through = ?2 WHERE id = ?3",
+            rusqlite::params![summary, pending.through, turn.conv_id],
+        );
+    }
+    
+    turn.messages_for_api.insert(pending.insert_at, summary_message(summary));
+    turn.context.estimated_tokens = context::estimate_total(&turn.messages_for_api) as u32;
+    turn.context.summarized = true;
+}
+
+fn finish_turn(
+    db: &Database,
+    conv_id: i64,
+    user_msg_id: i64,
+    response_content: &str,
+    stopped: bool,
+    model: &str,
+    context: ContextReport,
+) -> Result<(Conversation, Message, Message, ContextReport), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    let assistant_msg_id = branches::insert_message(
+        &conn,
+        NewMessage {
+            conversation_id: conv_id,
+            parent_message_id: Some(user_msg_id),
+            role: "assistant",
+            content: response_content,
+            stopped,
+            model: Some(model),
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    
+    let conversation: Conversation = conn
+        .query_row(
+            &format!("SELECT {} FROM conversations WHERE id = ?1", CONVERSATION_COLUMNS),
+            [conv_id],
+            conversation_from_row,
         )
         .map_err(|e| e.to_string())?;
+    
+    let user_message = branches::load_message(&conn, user_msg_id).map_err(|e| e.to_string())?;
+    let assistant_message = branches::load_message(&conn, assistant_msg_id).map_err(|e| e.to_string())?;
+    
+    Ok((conversation, user_message, assistant_message, context))
+}
+
+/// Tool-call rounds allowed before a turn gives up on a final answer.
+const MAX_TOOL_ITERATIONS: usize = 8;
+
+/// How long a tool call waits for the user before it counts as refused.
+const APPROVAL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Asks the user to allow a call to a tool that requires approval, through a
+/// `tool-approval-request` event. Refused and unanswered calls fail with an
+/// error the model gets as the tool result.
+async fn approve_tool_call(
+    app: &AppHandle,
+    tools: &ToolRegistry,
+    conversation_id: i64,
+    call: &ToolCall,
+) -> Result<(), String> {
+    if !tools.get(&call.function.name).is_some_and(|tool| tool.requires_approval()) {
+        return Ok(());
+    }
+    
+    let approvals = app.state::<ToolApprovals>();
+    let mut guard = approvals.request()?;
+    
+    let _ = app.emit(
+        "tool-approval-request",
+        ToolApprovalRequest {
+            id: guard.id,
+            conversation_id,
+            name: call.function.name.clone(),
+            arguments: call.function.arguments.clone(),
+        },
+    );
+    
+    match tokio::time::timeout(APPROVAL_TIMEOUT, &mut guard.response).await {
+        Ok(Ok(true)) => Ok(()),
+        Ok(_) => Err("O usuário recusou a execução da ferramenta".to_string()),
+        Err(_) => Err("Tempo esgotado aguardando a aprovação do usuário".to_string()),
+    }
+}
+
+/// Sends the turn with the registered tools advertised, runs the calls the
+/// model asks for and feeds the results back as `tool` messages until it
+/// answers without calling a tool.
+async fn run_tool_loop(app: &AppHandle, tools: &ToolRegistry, turn: &Turn) -> Result<String, String> {
+    let definitions = tools.definitions();
+    let mut messages = turn.messages_for_api.clone();
+    
+    for _ in 0..MAX_TOOL_ITERATIONS {
+        let reply = request_chat_message(
+            messages.clone(),
+            turn.api_url.clone(),
+            turn.api_key.clone(),
+            turn.model.clone(),
+            turn.params.clone(),
+            definitions.clone(),
+        )
+        .await?;
         
-        let user_msg_id = conn.last_insert_rowid();
+        let calls = reply.tool_calls.unwrap_or_default();
+        if calls.is_empty() {
+            return Ok(reply.content.unwrap_or_else(|| "Sem resposta".to_string()));
+        }
         
-        let mut stmt = conn
-            .prepare("SELECT id, conversation_id, role, content, created_at FROM messages WHERE conversation_id = ?1 ORDER BY created_at")
-            .map_err(|e| e.to_string())?;
+        messages.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: reply.content.unwrap_or_default().into(),
+            tool_calls: Some(calls.clone()),
+            ..Default::default()
+        });
         
-        let messages_for_api: Vec<ChatMessage> = stmt
-            .query_map([conv_id], |row| {
-                Ok(ChatMessage {
-                    role: row.get(2)?,
-                    content: row.get(3)?,
-                })
-            })
-            .map_err(|e| e.to_string())?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.to_string())?;
+        for call in calls {
+            let result = match approve_tool_call(app, tools, turn.conv_id, &call).await {
+                Ok(()) => tools.call(&call.function.name, &call.function.arguments).await,
+                Err(e) => Err(e),
+            };
+            let is_error = result.is_err();
+            let output = result.unwrap_or_else(|e| format!("Erro: {}", e));
+            
+            let _ = app.emit(
+                "chat-tool-call",
+                ToolCallEvent {
+                    conversation_id: turn.conv_id,
+                    name: call.function.name.clone(),
+                    arguments: call.function.arguments.clone(),
+                    result: output.clone(),
+                    is_error,
+                },
+            );
+            
+            messages.push(ChatMessage {
+                role: "tool".to_string(),
+                content: output.into(),
+                tool_call_id: Some(call.id),
+                ..Default::default()
+            });
+        }
+    }
+    
+    Err(format!(
+        "O modelo não chegou a uma resposta final após {} rodadas de ferramentas",
+        MAX_TOOL_ITERATIONS
+    ))
+}
+
+/// Requests a non-streaming reply for `turn` and stores it as the new leaf.
+async fn complete_turn(
+    app: &AppHandle,
+    db: &Database,
+    mut generation: GenerationGuard<'_>,
+    mut turn: Turn,
+) -> Result<(Conversation, Message, Message, ContextReport), String> {
+    let (conv_id, user_msg_id) = (turn.conv_id, turn.user_msg_id);
+    let title_job = title_job(&mut turn);
+    
+    tokio::select! {
+        _ = async {
+            summarize_overflow(db, &mut turn).await;
+            retrieve_context(db, &mut turn).await;
+        } => {}
+        _ = &mut generation.cancel => return Err(GENERATION_CANCELLED.to_string()),
+    }
+    
+    let response_content = if turn.tools_enabled {
+        let tools = app.state::<ToolRegistry>();
+        tokio::select! {
+            result = run_tool_loop(app, &tools, &turn) => result?,
+            _ = &mut generation.cancel => return Err(GENERATION_CANCELLED.to_string()),
+        }
+    } else {
+        tokio::select! {
+            result = request_chat_completion(
+                turn.messages_for_api,
+                turn.api_url,
+                turn.api_key,
+                turn.model.clone(),
+                turn.params,
+            ) => result?,
+            _ = &mut generation.cancel => return Err(GENERATION_CANCELLED.to_string()),
+        }
+    };
+    
+    let result = finish_turn(db, conv_id, user_msg_id, &response_content, false, &turn.model, turn.context)?;
+    
+    if let Some(job) = title_job {
+        spawn_title_job(app.clone(), job, result.1.content.clone(), result.2.content.clone());
+    }
+    
+    Ok(result)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_message_complete(
+    app: AppHandle,
+    db: State<'_, Database>,
+    generations: State<'_, Generations>,
+    conversation_id: Option<i64>,
+    user_input: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+    api_key: Option<String>,
+    model: Option<String>,
+    attachment_ids: Option<Vec<i64>>,
+) -> Result<(Conversation, Message, Message, ContextReport), String> {
+    let options = TurnOptions {
+        provider_id,
+        api_url,
+        api_key,
+        model,
+    };
+    // Claimed before anything is written, so a second send while a reply is
+    // running fails without leaving an unanswered message behind.
+    let claimed = conversation_id.map(|id| generations.register(id)).transpose()?;
+    let turn = begin_turn(&db, conversation_id, &user_input, &attachment_ids.unwrap_or_default(), options)?;
+    let generation = match claimed {
+        Some(generation) => generation,
+        None => generations.register(turn.conv_id)?,
+    };
+    
+    complete_turn(&app, &db, generation, turn).await
+}
+
+/// The reply to store once a stream ended or was cancelled, and whether it
+/// was stopped. Keeping the partial reply stores it even when nothing
+/// arrived yet, so the user message never ends up without an answer.
+fn streamed_reply(partial: String, cancelled: Option<CancelAction>) -> Result<(String, bool), String> {
+    match cancelled {
+        None if partial.is_empty() => Ok(("Sem resposta".to_string(), false)),
+        None => Ok((partial, false)),
+        Some(CancelAction::KeepPartial) => Ok((partial, true)),
+        Some(CancelAction::Discard) => Err(GENERATION_CANCELLED.to_string()),
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_message_stream(
+    window: tauri::Window,
+    db: State<'_, Database>,
+    generations: State<'_, Generations>,
+    conversation_id: Option<i64>,
+    user_input: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+    api_key: Option<String>,
+    model: Option<String>,
+    attachment_ids: Option<Vec<i64>>,
+) -> Result<(Conversation, Message, Message, ContextReport), String> {
+    let options = TurnOptions {
+        provider_id,
+        api_url,
+        api_key,
+        model,
+    };
+    let claimed = conversation_id.map(|id| generations.register(id)).transpose()?;
+    let mut turn = begin_turn(&db, conversation_id, &user_input, &attachment_ids.unwrap_or_default(), options)?;
+    let (conv_id, user_msg_id) = (turn.conv_id, turn.user_msg_id);
+    let title_job = title_job(&mut turn);
+    
+    let mut generation = match claimed {
+        Some(generation) => generation,
+        None => generations.register(conv_id)?,
+    };
+    let mut partial = String::new();
+    
+    let cancelled = tokio::select! {
+        _ = async {
+            summarize_overflow(&db, &mut turn).await;
+            retrieve_context(&db, &mut turn).await;
+        } => None,
+        action = &mut generation.cancel => Some(action.unwrap_or(CancelAction::Discard)),
+    };
+    
+    let cancelled = if cancelled.is_some() {
+        cancelled
+    } else {
+        let stream = chat_completion_stream(
+            turn.messages_for_api,
+            turn.api_url,
+            turn.api_key,
+            turn.model.clone(),
+            turn.params,
+            |delta| {
+                partial.push_str(delta);
+                let _ = window.emit(
+                    "chat-stream-delta",
+                    ChatDeltaEvent {
+                        conversation_id: conv_id,
+                        user_message_id: user_msg_id,
+                        delta: delta.to_string(),
+                    },
+                );
+            },
+        );
         
-        (conv_id, user_msg_id, messages_for_api)
+        tokio::select! {
+            result = stream => {
+                result?;
+                None
+            }
+            action = &mut generation.cancel => Some(action.unwrap_or(CancelAction::Discard)),
+        }
     };
     
-    let response_content = chat_completion(messages_for_api, api_url, api_key, model).await?;
+    let (content, stopped) = streamed_reply(partial, cancelled)?;
+    let (conversation, user_message, assistant_message, context) =
+        finish_turn(&db, conv_id, user_msg_id, &content, stopped, &turn.model, turn.context)?;
+    
+    let _ = window.emit(
+        "chat-stream-done",
+        ChatDoneEvent {
+            conversation_id: conv_id,
+            user_message_id: user_msg_id,
+            message: assistant_message.clone(),
+        },
+    );
     
-    let (conversation, user_message, assistant_message) = {
+    if let Some(job) = title_job {
+        spawn_title_job(
+            window.app_handle().clone(),
+            job,
+            user_message.content.clone(),
+            assistant_message.content.clone(),
+        );
+    }
+    
+    Ok((conversation, user_message, assistant_message, context))
+}
+
+#[tauri::command]
+pub async fn regenerate_message(
+    app: AppHandle,
+    db: State<'_, Database>,
+    generations: State<'_, Generations>,
+    message_id: i64,
+) -> Result<(Conversation, Message, Message, ContextReport), String> {
+    let (generation, turn) = {
         let conn = db.conn.lock().map_err(|e| e.to_string())?;
         
-        conn.execute(
-            "INSERT INTO messages (conversation_id, role, content) VALUES (?1, ?2, ?3)",
-            rusqlite::params![conv_id, "assistant", response_content],
+        let message = branches::load_message(&conn, message_id).map_err(|e| e.to_string())?;
+        if message.role != "assistant" {
+            return Err("Só é possível regenerar respostas do assistente".to_string());
+        }
+        
+        let parent_id = message
+            .parent_message_id
+            .ok_or_else(|| "Resposta sem mensagem de origem".to_string())?;
+        
+        let generation = generations.register(message.conversation_id)?;
+        let turn = prepare_turn(
+            &conn,
+            db.secrets.as_ref(),
+            message.conversation_id,
+            parent_id,
+            TurnOptions::default(),
+        )?;
+        
+        (generation, turn)
+    };
+    
+    complete_turn(&app, &db, generation, turn).await
+}
+
+/// Stores `content` as a new version of a past user message, next to the
+/// original, and generates a reply for it. The previous continuation stays
+/// reachable through `select_message_alternative`.
+#[tauri::command]
+pub async fn edit_message(
+    app: AppHandle,
+    db: State<'_, Database>,
+    generations: State<'_, Generations>,
+    message_id: i64,
+    content: String,
+) -> Result<(Conversation, Message, Message, ContextReport), String> {
+    if content.trim().is_empty() {
+        return Err("A mensagem não pode ficar vazia".to_string());
+    }
+    
+    let (generation, turn) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        
+        let message = branches::load_message(&conn, message_id).map_err(|e| e.to_string())?;
+        if message.role != "user" {
+            return Err("Só é possível editar mensagens do usuário".to_string());
+        }
+        
+        let generation = generations.register(message.conversation_id)?;
+        let user_msg_id = branches::insert_message(
+            &conn,
+            NewMessage {
+                conversation_id: message.conversation_id,
+                parent_message_id: message.parent_message_id,
+                role: "user",
+                content: &content,
+                stopped: false,
+                model: None,
+            },
         )
         .map_err(|e| e.to_string())?;
+        attachments::copy_to(&conn, message_id, user_msg_id).map_err(|e| e.to_string())?;
         
-        let assistant_msg_id = conn.last_insert_rowid();
+        let turn = prepare_turn(
+            &conn,
+            db.secrets.as_ref(),
+            message.conversation_id,
+            user_msg_id,
+            TurnOptions::default(),
+        )?;
         
-        let conversation: Conversation = conn
-            .query_row(
-                "SELECT id, title, created_at, updated_at FROM conversations WHERE id = ?1",
-                [conv_id],
-                |row| {
-                    Ok(Conversation {
-                        id: row.get(0)?,
-                        title: row.get(1)?,
-                        created_at: row.get(2)?,
-                        updated_at: row.get(3)?,
-                    })
-                },
-            )
-            .map_err(|e| e.to_string())?;
+        (generation, turn)
+    };
+    
+    complete_turn(&app, &db, generation, turn).await
+}
+
+#[tauri::command]
+pub fn cancel_generation(
+    generations: State<'_, Generations>,
+    conversation_id: i64,
+    keep_partial: bool,
+) -> Result<bool, String> {
+    let action = if keep_partial {
+        CancelAction::KeepPartial
+    } else {
+        CancelAction::Discard
+    };
+    
+    generations.cancel(conversation_id, action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn fallback_title_cuts_long_text_on_a_character_boundary() {
+        assert_eq!(
+            fallback_title("Qual é a melhor maneira de começar a aprender programação funcional em Haskell?"),
+            "Qual é a melhor maneira de começar a aprender prog..."
+        );
+        assert_eq!(
+            fallback_title("Explique ações, exceções e funções  🚀🚀🚀 em português com muitos detalhes"),
+            "Explique ações, exceções e funções  🚀🚀🚀 em portugu..."
+        );
+        assert_eq!(fallback_title(&"🙂".repeat(60)), format!("{}...", "🙂".repeat(50)));
+        assert_eq!(fallback_title("  \n Oi, tudo bem?\nSegunda linha"), "Oi, tudo bem?");
+        assert_eq!(fallback_title(" \n\t"), "Nova conversa");
+    }
+    
+    #[test]
+    fn clean_title_strips_quotes_and_prefixes() {
+        assert_eq!(clean_title("\"Receita de bolo\"").as_deref(), Some("Receita de bolo"));
+        assert_eq!(clean_title("\"Receita de bolo\".").as_deref(), Some("Receita de bolo"));
+        assert_eq!(clean_title("“Receita de bolo”").as_deref(), Some("Receita de bolo"));
+        assert_eq!(clean_title("'Ações em Rust'").as_deref(), Some("Ações em Rust"));
+        assert_eq!(clean_title("**Título:** Funções em Haskell.").as_deref(), Some("Funções em Haskell"));
+        assert_eq!(clean_title("## Título: Docker básico").as_deref(), Some("Docker básico"));
+        assert_eq!(clean_title("\n\n  Viagem ao Japão 🗾\nOutra linha").as_deref(), Some("Viagem ao Japão 🗾"));
+        assert_eq!(clean_title(".NET na prática").as_deref(), Some(".NET na prática"));
+        assert_eq!(clean_title("\"\"").as_deref(), None);
+        assert_eq!(clean_title("  \n ").as_deref(), None);
+        assert_eq!(clean_title(&"ç".repeat(300)).map(|t| t.chars().count()), Some(TITLE_MAX_CHARS * 2));
+    }
+    
+    #[test]
+    fn sse_line_with_content_is_a_delta() {
+        let line = r#"data: {"choices":[{"delta":{"content":"Olá"}}]}"#;
+        assert!(matches!(parse_sse_line(line), Ok(SseLine::Delta(delta)) if delta == "Olá"));
+    }
+    
+    #[test]
+    fn sse_line_joins_the_content_of_every_choice() {
+        let line = r#"data:{"choices":[{"delta":{"content":"a"}},{"delta":{"content":"b"}}]}"#;
+        assert!(matches!(parse_sse_line(line), Ok(SseLine::Delta(delta)) if delta == "ab"));
+    }
+    
+    #[test]
+    fn sse_done_marker_ends_the_stream() {
+        assert!(matches!(parse_sse_line("data: [DONE]"), Ok(SseLine::Done)));
+        assert!(matches!(parse_sse_line("data:[DONE]"), Ok(SseLine::Done)));
+    }
+    
+    #[test]
+    fn sse_lines_without_content_are_skipped() {
+        for line in [
+            "",
+            ": keep-alive",
+            "event: message",
+            r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#,
+            r#"data: {"choices":[]}"#,
+        ] {
+            assert!(matches!(parse_sse_line(line), Ok(SseLine::Skip)), "{:?}", line);
+        }
+    }
+    
+    #[test]
+    fn sse_line_with_invalid_json_is_an_error() {
+        assert!(parse_sse_line("data: {not json").is_err());
+    }
+    
+    #[test]
+    fn bound_url_does_not_receive_a_key_meant_for_another_url() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO providers (name, api_url) VALUES ('Remoto', 'https://a.example.com/v1')",
+            [],
+        )
+        .unwrap();
+        crate::commands::providers::save_provider_key(&conn, db.secrets.as_ref(), 2, "chave-a").unwrap();
+        conn.execute(
+            "INSERT INTO conversations (title, model, api_url) VALUES ('Teste', 'llama3', 'https://a.example.com/v1')",
+            [],
+        )
+        .unwrap();
+        let conv_id = conn.last_insert_rowid();
+        let user_msg_id = branches::insert_message(
+            &conn,
+            NewMessage {
+                conversation_id: conv_id,
+                parent_message_id: None,
+                role: "user",
+                content: "Oi",
+                stopped: false,
+                model: None,
+            },
+        )
+        .unwrap();
         
-        let user_message: Message = conn
-            .query_row(
-                "SELECT id, conversation_id, role, content, created_at FROM messages WHERE id = ?1",
-                [user_msg_id],
-                |row| {
-                    Ok(Message {
-                        id: row.get(0)?,
-                        conversation_id: row.get(1)?,
-                        role: row.get(2)?,
-                        content: row.get(3)?,
-                        created_at: row.get(4)?,
-                    })
-                },
-            )
-            .map_err(|e| e.to_string())?;
+        let options = |api_url: &str| TurnOptions {
+            api_url: Some(api_url.to_string()),
+            api_key: Some("chave-b".to_string()),
+            ..Default::default()
+        };
         
-        let assistant_message: Message = conn
-            .query_row(
-                "SELECT id, conversation_id, role, content, created_at FROM messages WHERE id = ?1",
-                [assistant_msg_id],
-                |row| {
-                    Ok(Message {
-                        id: row.get(0)?,
-                        conversation_id: row.get(1)?,
-                        role: row.get(2)?,
-                        content: row.get(3)?,
-                        created_at: row.get(4)?,
-                    })
+        let turn = prepare_turn(
+            &conn,
+            db.secrets.as_ref(),
+            conv_id,
+            user_msg_id,
+            options("https://b.example.com/v1"),
+        )
+        .unwrap();
+        assert_eq!(turn.api_url, "https://a.example.com/v1");
+        assert_eq!(turn.api_key, "chave-a");
+        
+        let turn = prepare_turn(
+            &conn,
+            db.secrets.as_ref(),
+            conv_id,
+            user_msg_id,
+            options("https://a.example.com/v1"),
+        )
+        .unwrap();
+        assert_eq!(turn.api_key, "chave-b");
+    }
+    
+    #[test]
+    fn streamed_reply_keeps_whatever_arrived() {
+        assert_eq!(streamed_reply("Olá".to_string(), None), Ok(("Olá".to_string(), false)));
+        assert_eq!(streamed_reply(String::new(), None), Ok(("Sem resposta".to_string(), false)));
+        assert_eq!(
+            streamed_reply("Ol".to_string(), Some(CancelAction::KeepPartial)),
+            Ok(("Ol".to_string(), true))
+        );
+        assert_eq!(
+            streamed_reply("Ol".to_string(), Some(CancelAction::Discard)),
+            Err(GENERATION_CANCELLED.to_string())
+        );
+    }
+    
+    #[test]
+    fn keeping_an_empty_partial_still_answers_the_user_message() {
+        let db = Database::in_memory();
+        let (conv_id, user_msg_id) = {
+            let conn = db.conn.lock().unwrap();
+            conn.execute("INSERT INTO conversations (title) VALUES ('Teste')", []).unwrap();
+            let conv_id = conn.last_insert_rowid();
+            let user_msg_id = branches::insert_message(
+                &conn,
+                NewMessage {
+                    conversation_id: conv_id,
+                    parent_message_id: None,
+                    role: "user",
+                    content: "Oi",
+                    stopped: false,
+                    model: None,
                 },
             )
-            .map_err(|e| e.to_string())?;
+            .unwrap();
+            (conv_id, user_msg_id)
+        };
         
-        (conversation, user_message, assistant_message)
-    };
-    
-    Ok((conversation, user_message, assistant_message))
+        let (content, stopped) = streamed_reply(String::new(), Some(CancelAction::KeepPartial)).unwrap();
+        let (conversation, _, reply, _) = finish_turn(
+            &db,
+            conv_id,
+            user_msg_id,
+            &content,
+            stopped,
+            "llama3",
+            ContextReport::default(),
+        )
+        .unwrap();
+        
+        assert_eq!(conversation.current_message_id, Some(reply.id));
+        assert_eq!(reply.parent_message_id, Some(user_msg_id));
+        assert!(reply.stopped);
+        assert_eq!(reply.content, "");
+    }
 }
@@ -1,23 +1,18 @@
+use rusqlite::Connection;
 use tauri::State;
-use crate::database::Database;
-use crate::models::Conversation;
+use crate::commands::providers::resolve_endpoint;
+use crate::database::{conversation_from_row, Database, CONVERSATION_COLUMNS};
+use crate::models::{Conversation, GenerationParams};
 
 #[tauri::command]
 pub fn get_conversations(db: State<Database>) -> Result<Vec<Conversation>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT id, title, created_at, updated_at FROM conversations ORDER BY updated_at DESC")
+        .prepare(&format!("SELECT {} FROM conversations ORDER BY updated_at DESC", CONVERSATION_COLUMNS))
         .map_err(|e| e.to_string())?;
     
     let conversations = stmt
-        .query_map([], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-            })
-        })
+        .query_map([], conversation_from_row)
         .map_err(|e| e.to_string())?
         .filter_map(|r| r.ok())
         .collect();
@@ -38,18 +33,11 @@ pub fn create_conversation(db: State<Database>, title: String) -> Result<Convers
     let id = conn.last_insert_rowid();
     
     let mut stmt = conn
-        .prepare("SELECT id, title, created_at, updated_at FROM conversations WHERE id = ?1")
+        .prepare(&format!("SELECT {} FROM conversations WHERE id = ?1", CONVERSATION_COLUMNS))
         .map_err(|e| e.to_string())?;
     
     let conversation = stmt
-        .query_row([id], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-            })
-        })
+        .query_row([id], conversation_from_row)
         .map_err(|e| e.to_string())?;
     
     Ok(conversation)
@@ -58,7 +46,10 @@ pub fn create_conversation(db: State<Database>, title: String) -> Result<Convers
 #[tauri::command]
 pub fn update_conversation_title(db: State<Database>, id: i64, title: String) -> Result<(), String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    
+    rename_conversation(&conn, id, &title)
+}
+
+pub fn rename_conversation(conn: &Connection, id: i64, title: &str) -> Result<(), String> {
     conn.execute(
         "UPDATE conversations SET title = ?1, updated_at = datetime('now') WHERE id = ?2",
         rusqlite::params![title, id],
@@ -68,10 +59,84 @@ pub fn update_conversation_title(db: State<Database>, id: i64, title: String) ->
     Ok(())
 }
 
+#[tauri::command]
+pub fn update_conversation_params(
+    db: State<Database>,
+    id: i64,
+    params: Option<GenerationParams>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    let params_json = params
+        .map(|p| serde_json::to_string(&p))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        "UPDATE conversations SET generation_params = ?1, updated_at = datetime('now') WHERE id = ?2",
+        rusqlite::params![params_json, id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_conversation_system_prompt(
+    db: State<Database>,
+    id: i64,
+    system_prompt: Option<String>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        "UPDATE conversations SET system_prompt = ?1, updated_at = datetime('now') WHERE id = ?2",
+        rusqlite::params![system_prompt, id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_conversation_model(
+    db: State<Database>,
+    id: i64,
+    model: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<Conversation, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    let endpoint = resolve_endpoint(&conn, db.secrets.as_ref(), provider_id, api_url, None)?;
+    
+    conn.execute(
+        "UPDATE conversations SET model = ?1, api_url = ?2, provider_id = ?3, updated_at = datetime('now') WHERE id = ?4",
+        rusqlite::params![model, endpoint.api_url, endpoint.provider_id, id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    conn.query_row(
+        &format!("SELECT {} FROM conversations WHERE id = ?1", CONVERSATION_COLUMNS),
+        [id],
+        conversation_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn delete_conversation(db: State<Database>, id: i64) -> Result<(), String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     
+    conn.execute(
+        "DELETE FROM attachments WHERE message_id IN (SELECT id FROM messages WHERE conversation_id = ?1)",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    conn.execute("DELETE FROM conversation_knowledge_bases WHERE conversation_id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
     conn.execute("DELETE FROM messages WHERE conversation_id = ?1", [id])
         .map_err(|e| e.to_string())?;
     
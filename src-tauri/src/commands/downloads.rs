@@ -0,0 +1,214 @@
+use rusqlite::Connection;
+use tauri::{AppHandle, State};
+use crate::database::{download_from_row, Database, DOWNLOAD_COLUMNS};
+use crate::downloads::{self, Downloads, StopAction};
+use crate::models::{Download, DownloadState};
+
+/// Queues a model pull. It starts right away when fewer than
+/// `max_concurrent_downloads` pulls are running.
+#[tauri::command]
+pub fn queue_model_download(
+    app: AppHandle,
+    model_name: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<Download, String> {
+    downloads::enqueue(&app, &model_name, provider_id, api_url)
+}
+
+/// Lists queued, running and paused downloads, plus completed, failed and
+/// cancelled ones when `include_finished` is set.
+#[tauri::command]
+pub fn list_downloads(db: State<Database>, include_finished: Option<bool>) -> Result<Vec<Download>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let filter = if include_finished.unwrap_or(false) {
+        ""
+    } else {
+        "WHERE state IN ('queued', 'downloading', 'paused')"
+    };
+    
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM downloads {} ORDER BY id", DOWNLOAD_COLUMNS, filter))
+        .map_err(|e| e.to_string())?;
+    
+    let downloads = stmt
+        .query_map([], download_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(downloads)
+}
+
+/// Pauses or cancels a download that is not running. Returns None when it
+/// is already in the target state.
+fn stop_waiting(conn: &Connection, id: i64, action: StopAction) -> Result<Option<Download>, String> {
+    let target = match action {
+        StopAction::Pause => DownloadState::Paused,
+        StopAction::Cancel => DownloadState::Cancelled,
+    };
+    
+    let download = downloads::load(conn, id)?;
+    
+    match (download.state, action) {
+        (DownloadState::Queued, _) | (DownloadState::Paused, StopAction::Cancel) => {
+            downloads::set_state(conn, id, target, None).map(Some)
+        }
+        (state, _) if state == target => Ok(None),
+        _ => Err("Este download já terminou".to_string()),
+    }
+}
+
+/// Pauses or cancels a download, whether it is running or still waiting.
+fn stop_download(
+    app: &AppHandle,
+    db: &Database,
+    downloads: &Downloads,
+    id: i64,
+    action: StopAction,
+) -> Result<(), String> {
+    if downloads.stop(id, action)? {
+        return Ok(());
+    }
+    
+    let download = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        match stop_waiting(&conn, id, action)? {
+            Some(download) => download,
+            None => return Ok(()),
+        }
+    };
+    
+    downloads::notify(app, &download);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn pause_download(
+    app: AppHandle,
+    db: State<Database>,
+    downloads: State<Downloads>,
+    id: i64,
+) -> Result<(), String> {
+    stop_download(&app, &db, &downloads, id, StopAction::Pause)
+}
+
+#[tauri::command]
+pub fn cancel_download(
+    app: AppHandle,
+    db: State<Database>,
+    downloads: State<Downloads>,
+    id: i64,
+) -> Result<(), String> {
+    stop_download(&app, &db, &downloads, id, StopAction::Cancel)
+}
+
+/// Puts a paused, failed or cancelled download back in the queue. Returns
+/// None when it is queued, running or completed already.
+fn requeue(conn: &Connection, id: i64) -> Result<Option<Download>, String> {
+    let download = downloads::load(conn, id)?;
+    
+    match download.state {
+        DownloadState::Queued | DownloadState::Downloading | DownloadState::Completed => Ok(None),
+        _ => downloads::set_state(conn, id, DownloadState::Queued, None).map(Some),
+    }
+}
+
+#[tauri::command]
+pub fn resume_download(app: AppHandle, db: State<Database>, id: i64) -> Result<Download, String> {
+    let download = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        match requeue(&conn, id)? {
+            Some(download) => download,
+            None => return downloads::load(&conn, id),
+        }
+    };
+    
+    downloads::notify(&app, &download);
+    downloads::pump(&app)?;
+    
+    Ok(download)
+}
+
+#[tauri::command]
+pub fn clear_finished_downloads(db: State<Database>) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        "DELETE FROM downloads WHERE state IN ('completed', 'failed', 'cancelled')",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn queued(conn: &Connection) -> i64 {
+        conn.execute("INSERT INTO downloads (model) VALUES ('llama3:8b')", []).unwrap();
+        conn.last_insert_rowid()
+    }
+    
+    fn state(conn: &Connection, id: i64) -> DownloadState {
+        downloads::load(conn, id).unwrap().state
+    }
+    
+    #[test]
+    fn queued_download_can_be_paused_and_resumed() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        let id = queued(&conn);
+        assert_eq!(state(&conn, id), DownloadState::Queued);
+        
+        let paused = stop_waiting(&conn, id, StopAction::Pause).unwrap().unwrap();
+        assert_eq!(paused.state, DownloadState::Paused);
+        assert!(stop_waiting(&conn, id, StopAction::Pause).unwrap().is_none());
+        
+        let resumed = requeue(&conn, id).unwrap().unwrap();
+        assert_eq!(resumed.state, DownloadState::Queued);
+        assert!(requeue(&conn, id).unwrap().is_none());
+        assert_eq!(state(&conn, id), DownloadState::Queued);
+    }
+    
+    #[test]
+    fn queued_download_can_be_cancelled() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        let id = queued(&conn);
+        let other = queued(&conn);
+        
+        let cancelled = stop_waiting(&conn, id, StopAction::Cancel).unwrap().unwrap();
+        
+        assert_eq!(cancelled.state, DownloadState::Cancelled);
+        assert_eq!(state(&conn, other), DownloadState::Queued);
+        assert!(stop_waiting(&conn, id, StopAction::Cancel).unwrap().is_none());
+        assert_eq!(
+            stop_waiting(&conn, id, StopAction::Pause).err().as_deref(),
+            Some("Este download já terminou")
+        );
+        assert_eq!(requeue(&conn, id).unwrap().unwrap().state, DownloadState::Queued);
+    }
+    
+    #[test]
+    fn paused_download_can_be_cancelled_but_finished_ones_stay() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        let id = queued(&conn);
+        
+        stop_waiting(&conn, id, StopAction::Pause).unwrap();
+        assert_eq!(stop_waiting(&conn, id, StopAction::Cancel).unwrap().unwrap().state, DownloadState::Cancelled);
+        
+        downloads::set_state(&conn, id, DownloadState::Completed, None).unwrap();
+        assert!(stop_waiting(&conn, id, StopAction::Cancel).is_err());
+        assert!(requeue(&conn, id).unwrap().is_none());
+        assert!(stop_waiting(&conn, 99, StopAction::Cancel).is_err());
+    }
+    
+    #[test]
+    fn stopping_a_download_that_is_not_running_falls_through() {
+        assert_eq!(Downloads::default().stop(1, StopAction::Pause), Ok(false));
+    }
+}
@@ -0,0 +1,183 @@
+use rusqlite::Connection;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tauri::State;
+use crate::branches;
+use crate::database::{
+    conversation_from_row, message_from_row, Database, CONVERSATION_COLUMNS, MESSAGE_COLUMNS,
+};
+use crate::export;
+use crate::models::{ExportFormat, ExportedConversation};
+
+/// JSON exports keep every branch of the message tree; the readable formats
+/// only show the branch currently selected in the app.
+fn load_conversation(
+    conn: &Connection,
+    conversation_id: i64,
+    format: ExportFormat,
+) -> Result<ExportedConversation, String> {
+    let conversation = conn
+        .query_row(
+            &format!("SELECT {} FROM conversations WHERE id = ?1", CONVERSATION_COLUMNS),
+            [conversation_id],
+            conversation_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let messages = if format == ExportFormat::Json {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM messages WHERE conversation_id = ?1 ORDER BY id ASC",
+                MESSAGE_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        
+        let messages = stmt
+            .query_map([conversation_id], message_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        messages
+    } else {
+        branches::active_branch(conn, conversation_id).map_err(|e| e.to_string())?
+    };
+    
+    Ok(ExportedConversation {
+        conversation,
+        messages,
+    })
+}
+
+/// Writes to a temporary file next to `path` and renames it into place, so a
+/// failed export never leaves a truncated file over an older one.
+fn write_export(path: &str, content: &str) -> Result<(), String> {
+    let path = Path::new(path);
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    
+    let written = fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Erro ao salvar exportação: {}", e));
+    }
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub fn export_conversation(
+    db: State<Database>,
+    conversation_id: i64,
+    format: ExportFormat,
+    path: String,
+) -> Result<(), String> {
+    let exported = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        load_conversation(&conn, conversation_id, format)?
+    };
+    
+    let content = export::render(vec![exported], format)?;
+    write_export(&path, &content)
+}
+
+#[tauri::command]
+pub fn export_all_conversations(
+    db: State<Database>,
+    format: ExportFormat,
+    path: String,
+) -> Result<usize, String> {
+    let conversations = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        
+        let mut stmt = conn
+            .prepare("SELECT id FROM conversations ORDER BY created_at ASC, id ASC")
+            .map_err(|e| e.to_string())?;
+        
+        let ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        
+        ids.into_iter()
+            .map(|id| load_conversation(&conn, id, format))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    
+    let count = conversations.len();
+    let content = export::render(conversations, format)?;
+    write_export(&path, &content)?;
+    
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branches::{insert_message, NewMessage};
+    
+    fn message<'a>(parent_message_id: Option<i64>, role: &'a str, content: &'a str) -> NewMessage<'a> {
+        NewMessage {
+            conversation_id: 1,
+            parent_message_id,
+            role,
+            content,
+            stopped: false,
+            model: None,
+        }
+    }
+    
+    #[test]
+    fn json_export_keeps_every_branch() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        conn.execute("INSERT INTO conversations (id, title) VALUES (1, 'Ramos')", []).unwrap();
+        
+        let question = insert_message(&conn, message(None, "user", "pergunta")).unwrap();
+        let first = insert_message(&conn, message(Some(question), "assistant", "primeira")).unwrap();
+        let second = insert_message(&conn, message(Some(question), "assistant", "segunda")).unwrap();
+        
+        let json = export::render(vec![load_conversation(&conn, 1, ExportFormat::Json).unwrap()], ExportFormat::Json)
+            .unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        
+        assert_eq!(document["format"], "llmpad");
+        assert_eq!(document["version"], 2);
+        let conversation = &document["conversations"][0];
+        assert_eq!(conversation["current_message_id"], second);
+        let ids: Vec<i64> = conversation["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ids, vec![question, first, second]);
+        assert_eq!(conversation["messages"][1]["parent_message_id"], question);
+        
+        let markdown = load_conversation(&conn, 1, ExportFormat::Markdown).unwrap();
+        let ids: Vec<i64> = markdown.messages.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![question, second]);
+    }
+    
+    #[test]
+    fn export_replaces_the_file_without_leaving_a_temp_file() {
+        let dir = std::env::temp_dir().join(format!("llmpad-export-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("conversa.md");
+        
+        write_export(path.to_str().unwrap(), "antiga").unwrap();
+        write_export(path.to_str().unwrap(), "nova").unwrap();
+        
+        assert_eq!(fs::read_to_string(&path).unwrap(), "nova");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(write_export(dir.join("faltando/conversa.md").to_str().unwrap(), "x").is_err());
+    }
+}
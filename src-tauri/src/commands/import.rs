@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use tauri::State;
+use crate::database::Database;
+use crate::import::{self, ParsedImport};
+use crate::models::{ImportReport, ImportSource};
+
+fn insert_all(tx: &rusqlite::Transaction, parsed: &ParsedImport) -> rusqlite::Result<Vec<i64>> {
+    let mut conversation_ids = Vec::with_capacity(parsed.conversations.len());
+    
+    for conversation in &parsed.conversations {
+        let first_message_at = conversation.messages.iter().find_map(|m| m.created_at.clone());
+        let last_message_at = conversation.messages.iter().rev().find_map(|m| m.created_at.clone());
+        let created_at = conversation.created_at.clone().or(first_message_at);
+        let updated_at = conversation
+            .updated_at
+            .clone()
+            .or(last_message_at)
+            .or_else(|| created_at.clone());
+        
+        tx.execute(
+            "INSERT INTO conversations (title, created_at, updated_at, system_prompt, model, generation_params)
+             VALUES (?1, COALESCE(?2, datetime('now')), COALESCE(?3, datetime('now')), ?4, ?5, ?6)",
+            rusqlite::params![
+                conversation.title,
+                created_at,
+                updated_at,
+                conversation.system_prompt,
+                conversation.model,
+                conversation.generation_params,
+            ],
+        )?;
+        
+        let conversation_id = tx.last_insert_rowid();
+        let mut last_created_at = created_at;
+        let mut previous_id: Option<i64> = None;
+        let mut id_map: HashMap<i64, i64> = HashMap::new();
+        
+        for message in &conversation.messages {
+            let parent_message_id = match message.source_id {
+                Some(_) => message.source_parent_id.and_then(|id| id_map.get(&id).copied()),
+                None => previous_id,
+            };
+
+            let message_created_at = message.created_at.clone().or_else(|| last_created_at.clone());
+            
+            tx.execute(
+                "INSERT INTO messages (conversation_id, parent_message_id, role, content, created_at, stopped, model)
+                 VALUES (?1, ?2, ?3, ?4, COALESCE(?5, datetime('now')), ?6, ?7)",
+                rusqlite::params![
+                    conversation_id,
+                    parent_message_id,
+                    message.role,
+                    message.content,
+                    message_created_at,
+                    message.stopped,
+                    message.model,
+                ],
+            )?;
+            
+            let id = tx.last_insert_rowid();
+            if let Some(source_id) = message.source_id {
+                id_map.insert(source_id, id);
+            }
+            previous_id = Some(id);
+            last_created_at = message_created_at;
+        }
+        
+        let current_message_id = conversation
+            .current_source_id
+            .and_then(|id| id_map.get(&id).copied())
+            .or(previous_id);
+        
+        tx.execute(
+            "UPDATE conversations SET current_message_id = ?1 WHERE id = ?2",
+            rusqlite::params![current_message_id, conversation_id],
+        )?;
+        
+        conversation_ids.push(conversation_id);
+    }
+    
+    Ok(conversation_ids)
+}
+
+#[tauri::command]
+pub fn import_conversations(
+    db: State<Database>,
+    path: String,
+    source: Option<ImportSource>,
+    dry_run: bool,
+) -> Result<ImportReport, String> {
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Erro ao ler arquivo de importação: {}", e))?;
+    
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Arquivo de importação não é um JSON válido: {}", e))?;
+    
+    let source = match source {
+        Some(source) => source,
+        None => import::detect_source(&value)
+            .ok_or_else(|| "Formato de importação não reconhecido".to_string())?,
+    };
+    
+    let parsed = import::parse(value, source)?;
+    
+    let conversation_ids = if dry_run {
+        vec![]
+    } else {
+        let mut conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let ids = insert_all(&tx, &parsed).map_err(|e| format!("Erro ao importar conversas: {}", e))?;
+        tx.commit().map_err(|e| e.to_string())?;
+        ids
+    };
+    
+    Ok(ImportReport {
+        source,
+        dry_run,
+        conversations: parsed.conversations.len(),
+        messages: parsed.conversations.iter().map(|c| c.messages.len()).sum(),
+        skipped_messages: parsed.skipped_messages,
+        conversation_ids,
+        warnings: parsed.warnings,
+    })
+}
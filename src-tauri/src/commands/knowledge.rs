@@ -0,0 +1,251 @@
+use rusqlite::Connection;
+use std::path::Path;
+use tauri::{Emitter, State};
+use crate::attachments;
+use crate::commands::ollama::embed;
+use crate::commands::providers::{resolve_endpoint, Endpoint};
+use crate::database::{knowledge_base_from_row, Database, KNOWLEDGE_BASE_COLUMNS};
+use crate::documents;
+use crate::knowledge;
+use crate::models::{IngestProgressEvent, IngestReport, KnowledgeBase};
+use crate::secrets::SecretStore;
+
+/// Inputs sent to `/api/embed` in a single request.
+const EMBED_BATCH_SIZE: usize = 32;
+
+pub fn load_knowledge_base(conn: &Connection, id: i64) -> Result<KnowledgeBase, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM knowledge_bases WHERE id = ?1", KNOWLEDGE_BASE_COLUMNS),
+        [id],
+        knowledge_base_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Endpoint used to embed the chunks of a knowledge base and the queries
+/// run against it.
+pub fn knowledge_endpoint(
+    conn: &Connection,
+    secrets: &dyn SecretStore,
+    knowledge_base: &KnowledgeBase,
+) -> Result<Endpoint, String> {
+    resolve_endpoint(
+        conn,
+        secrets,
+        knowledge_base.provider_id,
+        knowledge_base.api_url.clone(),
+        None,
+    )
+}
+
+#[tauri::command]
+pub fn get_knowledge_bases(db: State<Database>) -> Result<Vec<KnowledgeBase>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM knowledge_bases ORDER BY name COLLATE NOCASE", KNOWLEDGE_BASE_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    
+    let knowledge_bases = stmt
+        .query_map([], knowledge_base_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(knowledge_bases)
+}
+
+/// Registers a folder as a knowledge base. Its files are only read and
+/// embedded by `ingest_knowledge_base`.
+#[tauri::command]
+pub fn create_knowledge_base(
+    db: State<Database>,
+    name: String,
+    source_path: String,
+    embedding_model: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<KnowledgeBase, String> {
+    if name.trim().is_empty() {
+        return Err("Informe um nome para a base de conhecimento".to_string());
+    }
+    if embedding_model.trim().is_empty() {
+        return Err("Informe o modelo de embeddings".to_string());
+    }
+    if !Path::new(&source_path).is_dir() {
+        return Err(format!("Pasta não encontrada: {}", source_path));
+    }
+    
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let endpoint = resolve_endpoint(&conn, db.secrets.as_ref(), provider_id, api_url, None)?;
+    let api_url = endpoint.provider_id.is_none().then_some(endpoint.api_url);
+    
+    conn.execute(
+        "INSERT INTO knowledge_bases (name, source_path, embedding_model, provider_id, api_url)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![name.trim(), source_path, embedding_model.trim(), endpoint.provider_id, api_url],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    load_knowledge_base(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn delete_knowledge_base(db: State<Database>, id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    conn.execute("DELETE FROM conversation_knowledge_bases WHERE knowledge_base_id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute("DELETE FROM knowledge_chunks WHERE knowledge_base_id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute("DELETE FROM knowledge_bases WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+/// Reads every file of the knowledge base folder, chunks and embeds it, and
+/// replaces the stored chunks once everything has been embedded. Emits
+/// `knowledge-base-progress` after each file.
+#[tauri::command]
+pub async fn ingest_knowledge_base(
+    window: tauri::Window,
+    db: State<'_, Database>,
+    id: i64,
+) -> Result<IngestReport, String> {
+    let (knowledge_base, endpoint) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let knowledge_base = load_knowledge_base(&conn, id)?;
+        let endpoint = knowledge_endpoint(&conn, db.secrets.as_ref(), &knowledge_base)?;
+        (knowledge_base, endpoint)
+    };
+    
+    let root = Path::new(&knowledge_base.source_path);
+    let files = knowledge::collect_files(root)?;
+    
+    let mut chunks: Vec<(String, usize, String, Vec<f32>)> = Vec::new();
+    let mut skipped_files = Vec::new();
+    let mut indexed_files = 0;
+    
+    for (index, path) in files.iter().enumerate() {
+        let source = path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string();
+        
+        let too_large = std::fs::metadata(path)
+            .map(|metadata| metadata.len() > documents::MAX_DOCUMENT_SIZE as u64)
+            .unwrap_or(true);
+        let bytes = if too_large { None } else { std::fs::read(path).ok() };
+        
+        let text = bytes
+            .filter(|bytes| attachments::sniff_image_mime(bytes).is_none())
+            .and_then(|bytes| documents::extract_text(&source, &bytes).ok())
+            .map(|document| document.text);
+        
+        match text {
+            Some(text) => {
+                let pieces = knowledge::chunk_text(&text);
+                let first = chunks.len();
+                
+                for batch in pieces.chunks(EMBED_BATCH_SIZE) {
+                    let vectors = embed(
+                        &endpoint.api_url,
+                        &endpoint.api_key,
+                        &knowledge_base.embedding_model,
+                        batch,
+                    )
+                    .await?;
+                    
+                    for (content, vector) in batch.iter().zip(vectors) {
+                        chunks.push((source.clone(), chunks.len() - first, content.clone(), vector));
+                    }
+                }
+                
+                indexed_files += 1;
+            }
+            None => skipped_files.push(source),
+        }
+        
+        let _ = window.emit(
+            "knowledge-base-progress",
+            IngestProgressEvent {
+                knowledge_base_id: id,
+                processed_files: index + 1,
+                total_files: files.len(),
+                chunks: chunks.len(),
+            },
+        );
+    }
+    
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    tx.execute("DELETE FROM knowledge_chunks WHERE knowledge_base_id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    for (source, chunk_index, content, vector) in &chunks {
+        tx.execute(
+            "INSERT INTO knowledge_chunks (knowledge_base_id, source, chunk_index, content, embedding)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![id, source, *chunk_index as i64, content, knowledge::encode_embedding(vector)],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    tx.execute(
+        "UPDATE knowledge_bases SET indexed_at = datetime('now'), updated_at = datetime('now') WHERE id = ?1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(IngestReport {
+        knowledge_base_id: id,
+        files: indexed_files,
+        chunks: chunks.len(),
+        skipped_files,
+    })
+}
+
+#[tauri::command]
+pub fn get_conversation_knowledge_bases(
+    db: State<Database>,
+    conversation_id: i64,
+) -> Result<Vec<KnowledgeBase>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    knowledge::attached_to(&conn, conversation_id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|id| load_knowledge_base(&conn, id))
+        .collect()
+}
+
+/// Replaces the knowledge bases searched when the conversation runs a turn.
+#[tauri::command]
+pub fn set_conversation_knowledge_bases(
+    db: State<Database>,
+    conversation_id: i64,
+    knowledge_base_ids: Vec<i64>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    tx.execute(
+        "DELETE FROM conversation_knowledge_bases WHERE conversation_id = ?1",
+        [conversation_id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    for knowledge_base_id in knowledge_base_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO conversation_knowledge_bases (conversation_id, knowledge_base_id) VALUES (?1, ?2)",
+            [conversation_id, knowledge_base_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use rusqlite::Connection;
+use tauri::{AppHandle, State};
+use crate::database::{mcp_server_from_row, Database, MCP_SERVER_COLUMNS};
+use crate::mcp::McpServers;
+use crate::models::{McpServer, McpServerInput, McpServerStatus, McpTransport};
+use crate::tools::ToolApprovals;
+
+fn load_mcp_server(conn: &Connection, id: i64) -> Result<McpServer, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM mcp_servers WHERE id = ?1", MCP_SERVER_COLUMNS),
+        [id],
+        mcp_server_from_row,
+    )
+    .map_err(|_| format!("Servidor MCP {} não encontrado", id))
+}
+
+fn validate_mcp_server(server: &McpServerInput) -> Result<(), String> {
+    if server.name.trim().is_empty() {
+        return Err("Informe um nome para o servidor MCP".to_string());
+    }
+    
+    match server.transport {
+        McpTransport::Stdio if server.command.as_deref().is_none_or(|command| command.trim().is_empty()) => {
+            Err("Informe o comando que inicia o servidor MCP".to_string())
+        }
+        McpTransport::Http
+            if !server
+                .url
+                .as_deref()
+                .is_some_and(|url| url.starts_with("http://") || url.starts_with("https://")) =>
+        {
+            Err("Informe a URL do servidor MCP (http:// ou https://)".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Starts the server when it is enabled and stops it otherwise, so a change
+/// to its configuration takes effect right away.
+async fn apply_mcp_server(app: &AppHandle, mcp: &McpServers, server: McpServer) {
+    if server.enabled {
+        mcp.start(app, server).await;
+    } else {
+        mcp.stop(server.id).await;
+    }
+}
+
+#[tauri::command]
+pub fn get_mcp_servers(db: State<Database>) -> Result<Vec<McpServer>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM mcp_servers ORDER BY name COLLATE NOCASE", MCP_SERVER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    
+    let servers = stmt
+        .query_map([], mcp_server_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(servers)
+}
+
+#[tauri::command]
+pub fn get_mcp_server_status(mcp: State<McpServers>) -> Vec<McpServerStatus> {
+    mcp.statuses()
+}
+
+#[tauri::command]
+pub async fn create_mcp_server(
+    app: AppHandle,
+    db: State<'_, Database>,
+    mcp: State<'_, McpServers>,
+    server: McpServerInput,
+) -> Result<McpServer, String> {
+    validate_mcp_server(&server)?;
+    
+    let created = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        
+        conn.execute(
+            "INSERT INTO mcp_servers (name, transport, command, args, env, url, enabled, require_approval)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                server.name.trim(),
+                server.transport.as_str(),
+                server.command,
+                serde_json::to_string(&server.args).map_err(|e| e.to_string())?,
+                serde_json::to_string(&server.env).map_err(|e| e.to_string())?,
+                server.url,
+                server.enabled,
+                server.require_approval,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        
+        load_mcp_server(&conn, conn.last_insert_rowid())?
+    };
+    
+    apply_mcp_server(&app, &mcp, created.clone()).await;
+    Ok(created)
+}
+
+#[tauri::command]
+pub async fn update_mcp_server(
+    app: AppHandle,
+    db: State<'_, Database>,
+    mcp: State<'_, McpServers>,
+    id: i64,
+    server: McpServerInput,
+) -> Result<McpServer, String> {
+    validate_mcp_server(&server)?;
+    
+    let updated = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        
+        let changed = conn
+            .execute(
+                "UPDATE mcp_servers SET name = ?1, transport = ?2, command = ?3, args = ?4, env = ?5, url = ?6,
+                 enabled = ?7, require_approval = ?8, updated_at = datetime('now') WHERE id = ?9",
+                rusqlite::params![
+                    server.name.trim(),
+                    server.transport.as_str(),
+                    server.command,
+                    serde_json::to_string(&server.args).map_err(|e| e.to_string())?,
+                    serde_json::to_string(&server.env).map_err(|e| e.to_string())?,
+                    server.url,
+                    server.enabled,
+                    server.require_approval,
+                    id,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        
+        if changed == 0 {
+            return Err(format!("Servidor MCP {} não encontrado", id));
+        }
+        
+        load_mcp_server(&conn, id)?
+    };
+    
+    apply_mcp_server(&app, &mcp, updated.clone()).await;
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn delete_mcp_server(
+    db: State<'_, Database>,
+    mcp: State<'_, McpServers>,
+    id: i64,
+) -> Result<(), String> {
+    mcp.stop(id).await;
+    mcp.forget(id);
+    
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM mcp_servers WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+/// Turns a server on or off. Its tools are only offered to models while it
+/// is enabled and connected.
+#[tauri::command]
+pub async fn set_mcp_server_enabled(
+    app: AppHandle,
+    db: State<'_, Database>,
+    mcp: State<'_, McpServers>,
+    id: i64,
+    enabled: bool,
+) -> Result<McpServer, String> {
+    let server = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        
+        conn.execute(
+            "UPDATE mcp_servers SET enabled = ?1, updated_at = datetime('now') WHERE id = ?2",
+            rusqlite::params![enabled, id],
+        )
+        .map_err(|e| e.to_string())?;
+        
+        load_mcp_server(&conn, id)?
+    };
+    
+    apply_mcp_server(&app, &mcp, server.clone()).await;
+    Ok(server)
+}
+
+/// Reconnects an enabled server, e.g. after it gave up restarting or to pick
+/// up tools it added.
+#[tauri::command]
+pub async fn restart_mcp_server(
+    app: AppHandle,
+    db: State<'_, Database>,
+    mcp: State<'_, McpServers>,
+    id: i64,
+) -> Result<(), String> {
+    let server = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        load_mcp_server(&conn, id)?
+    };
+    
+    if !server.enabled {
+        return Err("O servidor MCP está desativado".to_string());
+    }
+    
+    mcp.start(&app, server).await;
+    Ok(())
+}
+
+/// Answers a `tool-approval-request`. Returns false when the call is no longer
+/// waiting (it timed out or the generation was cancelled).
+#[tauri::command]
+pub fn respond_tool_approval(approvals: State<ToolApprovals>, id: u64, approved: bool) -> Result<bool, String> {
+    approvals.respond(id, approved)
+}
@@ -1,29 +1,13 @@
 use tauri::State;
+use crate::branches::{self, NewMessage};
 use crate::database::Database;
 use crate::models::Message;
 
 #[tauri::command]
 pub fn get_messages(db: State<Database>, conversation_id: i64) -> Result<Vec<Message>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare("SELECT id, conversation_id, role, content, created_at FROM messages WHERE conversation_id = ?1 ORDER BY created_at ASC")
-        .map_err(|e| e.to_string())?;
-    
-    let messages = stmt
-        .query_map([conversation_id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                created_at: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
     
-    Ok(messages)
+    branches::active_branch(&conn, conversation_id).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -35,35 +19,42 @@ pub fn save_message(
 ) -> Result<Message, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     
-    conn.execute(
-        "INSERT INTO messages (conversation_id, role, content) VALUES (?1, ?2, ?3)",
-        rusqlite::params![conversation_id, role, content],
+    let parent_message_id = branches::current_message_id(&conn, conversation_id)
+        .map_err(|e| e.to_string())?;
+    
+    let id = branches::insert_message(
+        &conn,
+        NewMessage {
+            conversation_id,
+            parent_message_id,
+            role: &role,
+            content: &content,
+            stopped: false,
+            model: None,
+        },
     )
     .map_err(|e| e.to_string())?;
     
-    let id = conn.last_insert_rowid();
+    branches::load_message(&conn, id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_message_alternatives(db: State<Database>, message_id: i64) -> Result<Vec<Message>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
     
-    conn.execute(
-        "UPDATE conversations SET updated_at = datetime('now') WHERE id = ?1",
-        [conversation_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let message = branches::load_message(&conn, message_id).map_err(|e| e.to_string())?;
     
-    let mut stmt = conn
-        .prepare("SELECT id, conversation_id, role, content, created_at FROM messages WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+    branches::siblings(&conn, &message).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn select_message_alternative(db: State<Database>, message_id: i64) -> Result<Vec<Message>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
     
-    let message = stmt
-        .query_row([id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                created_at: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+    let message = branches::load_message(&conn, message_id).map_err(|e| e.to_string())?;
+    let leaf = branches::newest_leaf(&conn, message_id).map_err(|e| e.to_string())?;
+    
+    branches::set_current(&conn, message.conversation_id, leaf).map_err(|e| e.to_string())?;
     
-    Ok(message)
+    branches::active_branch(&conn, message.conversation_id).map_err(|e| e.to_string())
 }
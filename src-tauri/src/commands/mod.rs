@@ -1,11 +1,29 @@
 pub mod conversations;
 pub mod messages;
 pub mod settings;
+pub mod providers;
 pub mod chat;
 pub mod ollama;
+pub mod modelfiles;
+pub mod search;
+pub mod export;
+pub mod import;
+pub mod attachments;
+pub mod knowledge;
+pub mod mcp;
+pub mod downloads;
 
 pub use conversations::*;
 pub use messages::*;
 pub use settings::*;
+pub use providers::*;
 pub use chat::*;
 pub use ollama::*;
+pub use modelfiles::*;
+pub use search::*;
+pub use export::*;
+pub use import::*;
+pub use attachments::*;
+pub use knowledge::*;
+pub use mcp::*;
+pub use downloads::*;
@@ -0,0 +1,325 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use crate::modelfile::{self, Instruction};
+use crate::models::{ModelFile, ModelfileFields};
+
+const EXTENSION: &str = "Modelfile";
+
+/// Folder holding the `*.Modelfile` files: `Documents/LLMpad/models` when it
+/// exists, otherwise `models` inside the app data folder, created on demand.
+pub fn models_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+    
+    let mut models_dir = app_dir.parent()
+        .and_then(|p| p.parent())
+        .and_then(|p| p.parent())
+        .map(|p| p.join("Documents").join("LLMpad").join("models"))
+        .unwrap_or_else(|| app_dir.join("models"));
+    
+    if !models_dir.exists() {
+        models_dir = app_dir.join("models");
+    }
+    
+    if !models_dir.exists() {
+        fs::create_dir_all(&models_dir).map_err(|e| e.to_string())?;
+    }
+    
+    Ok(models_dir)
+}
+
+/// Characters Windows does not allow in file names. They are refused on every
+/// system so the models folder can be copied between them.
+const RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves whatever the extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Path of the Modelfile called `name`. The name becomes a file name, so it
+/// may not leave the models folder (path separators, `..`, a leading dot) or
+/// use what file systems reject; anything else is allowed.
+fn modelfile_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let valid = !name.is_empty()
+        && name.len() <= 200
+        && !name.starts_with('.')
+        && !name.contains("..")
+        && !name.chars().any(|c| c.is_control() || RESERVED_CHARS.contains(&c))
+        && !RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(name));
+    if !valid {
+        return Err(format!(
+            "Nome de Modelfile inválido: \"{}\" (não use /, \\, .., ponto no início nem < > : \" | ? *)",
+            name
+        ));
+    }
+    
+    Ok(dir.join(format!("{}.{}", name, EXTENSION)))
+}
+
+fn validate_content(content: &str) -> Result<(), String> {
+    match modelfile::parse(content).errors().next() {
+        Some(error) => Err(format!("Modelfile inválido: {}", error)),
+        None => Ok(()),
+    }
+}
+
+/// Writes to a temporary file in the same folder and renames it over `path`,
+/// so a failed write never leaves a truncated Modelfile behind.
+fn write_atomically(path: &Path, content: &str) -> Result<(), String> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    
+    let written = fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Erro ao salvar Modelfile: {}", e));
+    }
+    
+    Ok(())
+}
+
+/// Creates `path` with `content`, failing with `AlreadyExists` when it is
+/// there. The check and the creation are one step, so two saves cannot
+/// overwrite each other; a failed write removes the partial file.
+fn write_new(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    
+    let written = file.write_all(content.as_bytes()).and_then(|_| file.sync_all());
+    if written.is_err() {
+        drop(file);
+        let _ = fs::remove_file(path);
+    }
+    
+    written
+}
+
+/// Moves `path` to `new_path`, failing with `AlreadyExists` when another file
+/// is there. The new name is claimed with a hard link, so the check and the
+/// move cannot race with another save. When both paths already name the same
+/// file (renaming "modelo" to "Modelo" on a case-insensitive file system),
+/// a plain rename changes the case.
+fn rename_file(path: &Path, new_path: &Path) -> std::io::Result<()> {
+    if fs::canonicalize(new_path).ok() == Some(fs::canonicalize(path)?) {
+        return fs::rename(path, new_path);
+    }
+    
+    fs::hard_link(path, new_path)?;
+    
+    if let Err(e) = fs::remove_file(path) {
+        let _ = fs::remove_file(new_path);
+        return Err(e);
+    }
+    
+    Ok(())
+}
+
+fn saved(name: &str, path: PathBuf, content: String) -> ModelFile {
+    ModelFile {
+        name: name.to_string(),
+        path: path.to_string_lossy().into_owned(),
+        content,
+    }
+}
+
+fn write_new_modelfile(app: &AppHandle, name: &str, content: String) -> Result<ModelFile, String> {
+    let name = name.trim();
+    let path = modelfile_path(&models_dir(app)?, name)?;
+    validate_content(&content)?;
+    
+    match write_new(&path, &content) {
+        Ok(()) => Ok(saved(name, path, content)),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            Err(format!("Já existe um Modelfile chamado \"{}\"", name))
+        }
+        Err(e) => Err(format!("Erro ao salvar Modelfile: {}", e)),
+    }
+}
+
+/// Creates a Modelfile from raw text.
+#[tauri::command]
+pub fn create_modelfile(app: AppHandle, name: String, content: String) -> Result<ModelFile, String> {
+    write_new_modelfile(&app, &name, content)
+}
+
+/// Creates a Modelfile from a base model, parameters, system prompt and
+/// template.
+#[tauri::command]
+pub fn create_modelfile_from_fields(
+    app: AppHandle,
+    name: String,
+    fields: ModelfileFields,
+) -> Result<ModelFile, String> {
+    let mut instructions = vec![Instruction::From {
+        model: fields.base_model.trim().to_string(),
+    }];
+    
+    instructions.extend(fields.parameters.into_iter().map(|parameter| Instruction::Parameter {
+        name: parameter.name,
+        value: parameter.value,
+    }));
+    
+    if let Some(template) = fields.template.filter(|t| !t.trim().is_empty()) {
+        instructions.push(Instruction::Template { template });
+    }
+    
+    if let Some(system) = fields.system.filter(|s| !s.trim().is_empty()) {
+        instructions.push(Instruction::System { system });
+    }
+    
+    write_new_modelfile(&app, &name, modelfile::render(&instructions))
+}
+
+/// Replaces the content of an existing Modelfile.
+#[tauri::command]
+pub fn save_modelfile(app: AppHandle, name: String, content: String) -> Result<ModelFile, String> {
+    let path = modelfile_path(&models_dir(&app)?, &name)?;
+    validate_content(&content)?;
+    
+    if !path.is_file() {
+        return Err(format!("Modelfile \"{}\" não encontrado", name));
+    }
+    
+    write_atomically(&path, &content)?;
+    Ok(saved(&name, path, content))
+}
+
+#[tauri::command]
+pub fn rename_modelfile(app: AppHandle, name: String, new_name: String) -> Result<ModelFile, String> {
+    let dir = models_dir(&app)?;
+    let path = modelfile_path(&dir, &name)?;
+    let new_name = new_name.trim();
+    let new_path = modelfile_path(&dir, new_name)?;
+    
+    if !path.is_file() {
+        return Err(format!("Modelfile \"{}\" não encontrado", name));
+    }
+    
+    match rename_file(&path, &new_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(format!("Já existe um Modelfile chamado \"{}\"", new_name));
+        }
+        Err(e) => return Err(format!("Erro ao renomear Modelfile: {}", e)),
+    }
+    let content = fs::read_to_string(&new_path).map_err(|e| e.to_string())?;
+    
+    Ok(saved(new_name, new_path, content))
+}
+
+#[tauri::command]
+pub fn delete_modelfile(app: AppHandle, name: String) -> Result<(), String> {
+    let path = modelfile_path(&models_dir(&app)?, &name)?;
+    
+    if !path.is_file() {
+        return Err(format!("Modelfile \"{}\" não encontrado", name));
+    }
+    
+    fs::remove_file(&path).map_err(|e| format!("Erro ao excluir Modelfile: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("llmpad-modelfiles-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+    
+    #[test]
+    fn modelfile_names_stay_inside_the_models_folder() {
+        let dir = Path::new("/models");
+        
+        assert_eq!(modelfile_path(dir, "meu-modelo").unwrap(), dir.join("meu-modelo.Modelfile"));
+        assert_eq!(modelfile_path(dir, "Assistente de código v2").unwrap(), dir.join("Assistente de código v2.Modelfile"));
+        assert_eq!(modelfile_path(dir, "llama3.1 ajustado").unwrap(), dir.join("llama3.1 ajustado.Modelfile"));
+        
+        for name in ["", ".", "..", "../fora", "a/../b", "a..b", ".oculto", "a/b", "a\\b", "a:b", "a*b", "a\tb"] {
+            assert!(modelfile_path(dir, name).is_err(), "{:?}", name);
+        }
+    }
+    
+    #[test]
+    fn reserved_device_names_and_long_names_are_refused() {
+        let dir = Path::new("/models");
+        
+        for name in ["CON", "con", "Nul", "COM1", "lpt9"] {
+            assert!(modelfile_path(dir, name).is_err(), "{}", name);
+        }
+        assert!(modelfile_path(dir, "console").is_ok());
+        assert!(modelfile_path(dir, "COM10").is_ok());
+        
+        assert!(modelfile_path(dir, &"a".repeat(200)).is_ok());
+        assert!(modelfile_path(dir, &"a".repeat(201)).is_err());
+        assert!(modelfile_path(dir, &"ç".repeat(101)).is_err());
+    }
+    
+    #[test]
+    fn write_new_never_overwrites() {
+        let dir = temp_dir("write-new");
+        let path = dir.join("modelo.Modelfile");
+        
+        write_new(&path, "FROM llama3\n").unwrap();
+        let error = write_new(&path, "FROM mistral\n").unwrap_err();
+        
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "FROM llama3\n");
+        assert!(write_new(&dir.join("faltando/modelo.Modelfile"), "FROM llama3\n").is_err());
+    }
+    
+    #[test]
+    fn rename_moves_the_file_but_never_over_another() {
+        let dir = temp_dir("rename");
+        let path = dir.join("a.Modelfile");
+        let other = dir.join("b.Modelfile");
+        write_new(&path, "FROM llama3\n").unwrap();
+        write_new(&other, "FROM mistral\n").unwrap();
+        
+        let error = rename_file(&path, &other).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "FROM llama3\n");
+        assert_eq!(fs::read_to_string(&other).unwrap(), "FROM mistral\n");
+        
+        rename_file(&path, &dir.join("c.Modelfile")).unwrap();
+        assert_eq!(file_names(&dir), vec!["b.Modelfile", "c.Modelfile"]);
+        assert_eq!(fs::read_to_string(dir.join("c.Modelfile")).unwrap(), "FROM llama3\n");
+        
+        assert!(rename_file(&path, &dir.join("d.Modelfile")).is_err());
+    }
+    
+    #[test]
+    fn rename_changes_only_the_case_or_nothing() {
+        let dir = temp_dir("rename-case");
+        write_new(&dir.join("modelo.Modelfile"), "FROM llama3\n").unwrap();
+        
+        rename_file(&dir.join("modelo.Modelfile"), &dir.join("Modelo.Modelfile")).unwrap();
+        assert_eq!(file_names(&dir), vec!["Modelo.Modelfile"]);
+        
+        rename_file(&dir.join("Modelo.Modelfile"), &dir.join("Modelo.Modelfile")).unwrap();
+        assert_eq!(file_names(&dir), vec!["Modelo.Modelfile"]);
+        assert_eq!(fs::read_to_string(dir.join("Modelo.Modelfile")).unwrap(), "FROM llama3\n");
+    }
+}
@@ -1,37 +1,31 @@
+use std::collections::HashMap;
 use std::fs;
-use tauri::Emitter;
-use crate::models::{ModelFile, ModelFileInfo, OllamaListResponse};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager, State};
+use tokio::sync::broadcast::error::RecvError;
+use crate::commands::modelfiles::models_dir;
+use crate::commands::providers::resolve_endpoint;
+use crate::database::Database;
+use crate::downloads::{self, Downloads};
+use crate::modelfile::{self, Instruction};
+use crate::models::{
+    CreateProgressEvent, DownloadState, ModelFile, ModelFileInfo, OllamaListResponse, PullProgressEvent,
+};
 
 #[tauri::command]
 pub fn get_modelfiles(app: tauri::AppHandle) -> Result<Vec<ModelFile>, String> {
-    use tauri::Manager;
-    
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-    
-    let mut models_dir = app_dir.parent()
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .map(|p| p.join("Documents").join("LLMpad").join("models"))
-        .unwrap_or_else(|| app_dir.join("models"));
-    
-    if !models_dir.exists() {
-        models_dir = app_dir.join("models");
-    }
-    
-    if !models_dir.exists() {
-        fs::create_dir_all(&models_dir).map_err(|e| e.to_string())?;
-        return Ok(vec![]);
-    }
+    let models_dir = models_dir(&app)?;
     
     let mut modelfiles = Vec::new();
     
     if let Ok(entries) = fs::read_dir(&models_dir) {
         for entry in entries.flatten() {
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "Modelfile") {
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "Modelfile") {
                 let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
                 let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
                 modelfiles.push(ModelFile {
@@ -49,15 +43,12 @@ pub fn get_modelfiles(app: tauri::AppHandle) -> Result<Vec<ModelFile>, String> {
 #[tauri::command]
 pub async fn get_modelfiles_with_status(app: tauri::AppHandle, api_url: String) -> Result<Vec<ModelFileInfo>, String> {
     let modelfiles = get_modelfiles(app)?;
-    let ollama_models = list_ollama_models(api_url).await.unwrap_or_default();
+    let ollama_models = fetch_ollama_models(&api_url).await.unwrap_or_default();
     
     let mut result = Vec::new();
     for mf in modelfiles {
-        let base_model = mf.content
-            .lines()
-            .find(|line| line.trim().to_uppercase().starts_with("FROM "))
-            .and_then(|line| line.split_whitespace().nth(1))
-            .map(|s| s.to_string());
+        let parsed = modelfile::parse(&mf.content);
+        let base_model = parsed.from().map(str::to_string);
         
         let is_base_available = base_model
             .as_ref()
@@ -70,6 +61,8 @@ pub async fn get_modelfiles_with_status(app: tauri::AppHandle, api_url: String)
             content: mf.content,
             base_model,
             is_base_available,
+            parameters: parsed.parameters(),
+            diagnostics: parsed.diagnostics,
         });
     }
     
@@ -77,23 +70,40 @@ pub async fn get_modelfiles_with_status(app: tauri::AppHandle, api_url: String)
 }
 
 #[tauri::command]
-pub async fn list_ollama_models(api_url: String) -> Result<Vec<String>, String> {
-    let client = reqwest::Client::new();
-    let base_url = api_url.trim_end_matches("/v1").trim_end_matches('/');
-    let url = format!("{}/api/tags", base_url);
+pub async fn list_ollama_models(
+    db: State<'_, Database>,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<Vec<String>, String> {
+    let endpoint = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        resolve_endpoint(&conn, db.secrets.as_ref(), provider_id, api_url, None)?
+    };
+    
+    fetch_ollama_models(&endpoint.api_url).await
+}
+
+/// Native Ollama API root for an OpenAI-compatible `.../v1` URL.
+pub fn ollama_base_url(api_url: &str) -> &str {
+    api_url.trim_end_matches('/').trim_end_matches("/v1").trim_end_matches('/')
+}
 
+pub async fn fetch_ollama_models(api_url: &str) -> Result<Vec<String>, String> {
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/tags", ollama_base_url(api_url));
+    
     let response = client
         .get(&url)
         .send()
         .await
         .map_err(|e| format!("Erro ao conectar com Ollama: {}", e))?;
-
+    
     if !response.status().is_success() {
         let status = response.status();
         let text = response.text().await.unwrap_or_default();
         return Err(format!("Ollama retornou erro {}: {}", status, text));
     }
-
+    
     let ollama_response: OllamaListResponse = response
         .json()
         .await
@@ -102,137 +112,791 @@ pub async fn list_ollama_models(api_url: String) -> Result<Vec<String>, String>
     Ok(ollama_response.models.into_iter().map(|m| m.name).collect())
 }
 
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// Embeds `inputs` with Ollama's `/api/embed`, one vector per input.
+pub async fn embed(api_url: &str, api_key: &str, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/embed", ollama_base_url(api_url));
+    
+    let mut request = client.post(&url).json(&EmbedRequest { model, input: inputs });
+    if !api_key.is_empty() {
+        request = request.header("Authorization", format!("Bearer {}", api_key));
+    }
+    
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Erro ao conectar com Ollama: {}", e))?;
+    
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama retornou erro {}: {}", status, text));
+    }
+    
+    let embed_response: EmbedResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Erro ao parsear resposta do Ollama: {}", e))?;
+    
+    if embed_response.embeddings.len() != inputs.len() {
+        return Err("Ollama retornou um número inesperado de embeddings".to_string());
+    }
+    
+    Ok(embed_response.embeddings)
+}
+
 #[tauri::command]
 pub async fn check_base_model(api_url: String, model_name: String) -> Result<bool, String> {
-    let models = list_ollama_models(api_url).await?;
+    let models = fetch_ollama_models(&api_url).await?;
     Ok(models.contains(&model_name))
 }
 
-#[tauri::command]
-pub async fn create_ollama_model(
-    _api_url: String,
-    model_name: String,
-    modelfile_content: String,
-) -> Result<String, String> {
-    use std::process::Command;
-    use std::io::Write;
+/// Why `create_ollama_model` failed. Serialized as `{ kind, message }` so the
+/// frontend can tell validation errors from connection or Ollama errors.
+#[derive(Debug)]
+pub enum CreateModelError {
+    InvalidName(String),
+    InvalidModelfile(String),
+    File(String),
+    Connection(String),
+    Ollama { status: u16, message: String },
+    Failed(String),
+    Interrupted,
+}
+
+impl CreateModelError {
+    fn kind(&self) -> &'static str {
+        match self {
+            CreateModelError::InvalidName(_) => "invalid_name",
+            CreateModelError::InvalidModelfile(_) => "invalid_modelfile",
+            CreateModelError::File(_) => "file",
+            CreateModelError::Connection(_) => "connection",
+            CreateModelError::Ollama { .. } => "ollama",
+            CreateModelError::Failed(_) => "failed",
+            CreateModelError::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl std::fmt::Display for CreateModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateModelError::InvalidName(reason) => write!(f, "Nome de modelo inválido: {}", reason),
+            CreateModelError::InvalidModelfile(reason) => write!(f, "Modelfile inválido: {}", reason),
+            CreateModelError::File(reason) => write!(f, "Erro ao ler arquivo do modelo: {}", reason),
+            CreateModelError::Connection(reason) => write!(f, "Erro ao conectar com Ollama: {}", reason),
+            CreateModelError::Ollama { status, message } => write!(f, "Ollama retornou erro {}: {}", status, message),
+            CreateModelError::Failed(reason) => write!(f, "Erro ao criar modelo: {}", reason),
+            CreateModelError::Interrupted => write!(f, "A criação do modelo foi interrompida antes de terminar"),
+        }
+    }
+}
+
+impl Serialize for CreateModelError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        
+        let mut error = serializer.serialize_struct("CreateModelError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+/// Checks a model name against Ollama's rules: `[host/][namespace/]model[:tag]`,
+/// where each part starts with a letter, digit or underscore and continues
+/// with those, `-` or `.` (no `.` in the namespace, `:` only in the host port).
+pub fn validate_model_name(name: &str) -> Result<(), String> {
+    let (path, tag) = match name.rsplit_once(':') {
+        Some((path, tag)) if !tag.contains('/') => (path, Some(tag)),
+        _ => (name, None),
+    };
+    
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() > 3 {
+        return Err("use no máximo host/namespace/modelo:tag".to_string());
+    }
     
-    let temp_dir = std::env::temp_dir();
-    let modelfile_path = temp_dir.join(format!("{}.Modelfile", model_name));
+    let (host, namespace, model) = match parts.as_slice() {
+        [model] => (None, None, *model),
+        [namespace, model] => (None, Some(*namespace), *model),
+        [host, namespace, model] => (Some(*host), Some(*namespace), *model),
+        _ => unreachable!(),
+    };
     
-    let mut file = std::fs::File::create(&modelfile_path)
-        .map_err(|e| format!("Erro ao criar arquivo temporário: {}", e))?;
+    let valid = |part: &str, max: usize, allow_dot: bool, allow_colon: bool| {
+        let mut chars = part.chars();
+        part.len() <= max
+            && chars.next().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            && chars.all(|c| {
+                c.is_ascii_alphanumeric()
+                    || c == '_'
+                    || c == '-'
+                    || (c == '.' && allow_dot)
+                    || (c == ':' && allow_colon)
+            })
+    };
     
-    file.write_all(modelfile_content.as_bytes())
-        .map_err(|e| format!("Erro ao escrever Modelfile: {}", e))?;
+    if !valid(model, 80, true, false) {
+        return Err(format!("'{}' não é um nome de modelo válido", model));
+    }
+    if let Some(namespace) = namespace {
+        if !valid(namespace, 80, false, false) {
+            return Err(format!("'{}' não é um namespace válido", namespace));
+        }
+    }
+    if let Some(host) = host {
+        if !valid(host, 350, true, true) {
+            return Err(format!("'{}' não é um host válido", host));
+        }
+    }
+    if let Some(tag) = tag {
+        if !valid(tag, 80, true, false) {
+            return Err(format!("'{}' não é uma tag válida", tag));
+        }
+    }
     
-    drop(file);
+    Ok(())
+}
+
+#[derive(Serialize, Default)]
+struct CreateRequest {
+    model: String,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    files: HashMap<String, String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    adapters: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    license: Vec<String>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    parameters: serde_json::Map<String, Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    messages: Vec<CreateMessage>,
+}
+
+#[derive(Serialize)]
+struct CreateMessage {
+    role: String,
+    content: String,
+}
+
+/// Local weights referenced by FROM or ADAPTER, uploaded as blobs before the
+/// model is created.
+struct LocalFiles {
+    from: Option<PathBuf>,
+    adapter: Option<PathBuf>,
+}
+
+/// `FROM` and `ADAPTER` point to a local file or folder when they look like a
+/// path; anything else is a model name. `./` and `../` are relative to
+/// `base_dir`, the folder the Modelfiles live in, not to the working
+/// directory of the app.
+fn local_path(value: &str, base_dir: &Path) -> Option<PathBuf> {
+    let looks_like_path = value.starts_with("./")
+        || value.starts_with("../")
+        || value.starts_with('~')
+        || Path::new(value).is_absolute();
+    if !looks_like_path {
+        return None;
+    }
     
-    let output = Command::new("ollama")
-        .arg("create")
-        .arg(&model_name)
-        .arg("-f")
-        .arg(&modelfile_path)
-        .output()
-        .map_err(|e| format!("Erro ao executar comando ollama: {}. Certifique-se de que o Ollama está instalado.", e))?;
+    match value.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(rest)),
+        None => Some(base_dir.join(value)),
+    }
+}
+
+/// Translates a Modelfile into the structured body of `/api/create`. Any
+/// error diagnostic rejects the Modelfile.
+fn create_request(
+    model: &str,
+    content: &str,
+    base_dir: &Path,
+) -> Result<(CreateRequest, LocalFiles), CreateModelError> {
+    let modelfile = modelfile::parse(content);
+    if let Some(error) = modelfile.errors().next() {
+        return Err(CreateModelError::InvalidModelfile(error.to_string()));
+    }
     
-    let _ = std::fs::remove_file(&modelfile_path);
+    let mut request = CreateRequest {
+        model: model.to_string(),
+        stream: true,
+        ..Default::default()
+    };
+    let mut local = LocalFiles { from: None, adapter: None };
     
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        return Err(format!("Erro ao criar modelo:\n{}\n{}", stderr, stdout));
+    for statement in modelfile.statements {
+        match statement.instruction {
+            Instruction::From { model } => match local_path(&model, base_dir) {
+                Some(path) => local.from = Some(path),
+                None => request.from = Some(model),
+            },
+            Instruction::Adapter { path } => match local_path(&path, base_dir) {
+                Some(path) => local.adapter = Some(path),
+                None => {
+                    return Err(CreateModelError::InvalidModelfile(format!(
+                        "linha {}: ADAPTER deve apontar para um arquivo local",
+                        statement.line
+                    )))
+                }
+            },
+            Instruction::Template { template } => request.template = Some(template),
+            Instruction::System { system } => request.system = Some(system),
+            Instruction::License { license } => request.license.push(license),
+            Instruction::Parameter { name, value } => {
+                let value = serde_json::to_value(value).unwrap_or_default();
+                if name == "stop" {
+                    if let Value::Array(stops) = request.parameters.entry(name).or_insert_with(|| Value::Array(vec![])) {
+                        stops.push(value);
+                    }
+                } else {
+                    request.parameters.insert(name, value);
+                }
+            }
+            Instruction::Message { role, content } => request.messages.push(CreateMessage {
+                role: role.as_str().to_string(),
+                content,
+            }),
+        }
     }
     
-    Ok(format!("Modelo '{}' criado com sucesso!", model_name))
+    Ok((request, local))
 }
 
-#[tauri::command]
-pub async fn pull_ollama_model(model_name: String, window: tauri::Window) -> Result<String, String> {
-    use std::io::Read;
-    use std::process::{Command, Stdio};
-    use std::sync::mpsc;
-    
-    let mut child = Command::new("ollama")
-        .arg("pull")
-        .arg(&model_name)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Erro ao executar comando ollama: {}. Certifique-se de que o Ollama está instalado.", e))?;
-
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
-
-    let (tx, rx) = mpsc::channel::<String>();
-
-    let tx_stdout = tx.clone();
-    let stdout_handle = std::thread::spawn(move || {
-        if let Some(mut stdout) = stdout {
-            let mut buffer = [0u8; 1024];
-            let mut line_buffer = String::new();
-            
-            while let Ok(n) = stdout.read(&mut buffer) {
-                if n == 0 { break; }
-                
-                let chunk = String::from_utf8_lossy(&buffer[..n]);
-                line_buffer.push_str(&chunk);
+/// Time allowed to connect to Ollama and to check whether it has a blob.
+const BLOB_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time allowed to upload a blob: a minute plus one second per MiB, so large
+/// weights are not cut off while a stalled upload still ends.
+fn upload_timeout(size: u64) -> Duration {
+    Duration::from_secs(60 + size / (1024 * 1024))
+}
+
+/// Uploads a file, or every file of a folder, to `/api/blobs` unless Ollama
+/// already has it, and returns the file names mapped to their digests.
+async fn push_blobs(
+    base_url: String,
+    api_key: String,
+    path: PathBuf,
+) -> Result<HashMap<String, String>, CreateModelError> {
+    tokio::task::spawn_blocking(move || {
+        let files: Vec<PathBuf> = if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(&path)
+                .map_err(|e| CreateModelError::File(format!("{}: {}", path.display(), e)))?
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect();
+            files.sort();
+            files
+        } else if path.is_file() {
+            vec![path.clone()]
+        } else {
+            return Err(CreateModelError::File(format!("{} não encontrado", path.display())));
+        };
+        
+        let client = reqwest::blocking::Client::builder()
+            .connect_timeout(BLOB_CHECK_TIMEOUT)
+            .timeout(BLOB_CHECK_TIMEOUT)
+            .build()
+            .map_err(|e| CreateModelError::Connection(e.to_string()))?;
+        let mut digests = HashMap::new();
+        
+        for file in files {
+            let read_error = |e: std::io::Error| CreateModelError::File(format!("{}: {}", file.display(), e));
+            
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut fs::File::open(&file).map_err(read_error)?, &mut hasher).map_err(read_error)?;
+            let digest = format!("sha256:{:x}", hasher.finalize());
+            let url = format!("{}/api/blobs/{}", base_url, digest);
+            
+            let authorize = |request: reqwest::blocking::RequestBuilder| {
+                if api_key.is_empty() {
+                    request
+                } else {
+                    request.header("Authorization", format!("Bearer {}", api_key))
+                }
+            };
+            
+            let exists = authorize(client.head(&url))
+                .send()
+                .map_err(|e| CreateModelError::Connection(e.to_string()))?
+                .status()
+                .is_success();
+            
+            if !exists {
+                let upload = fs::File::open(&file).map_err(read_error)?;
+                let size = upload.metadata().map_err(read_error)?.len();
+                let response = authorize(client.post(&url))
+                    .timeout(upload_timeout(size))
+                    .body(upload)
+                    .send()
+                    .map_err(|e| CreateModelError::Connection(e.to_string()))?;
                 
-                while let Some(pos) = line_buffer.find(|c| c == '\n' || c == '\r') {
-                    let line = line_buffer[..pos].to_string();
-                    if !line.is_empty() {
-                        let _ = tx_stdout.send(line);
-                    }
-                    line_buffer = line_buffer[pos + 1..].to_string();
+                if !response.status().is_success() {
+                    return Err(CreateModelError::Ollama {
+                        status: response.status().as_u16(),
+                        message: response.text().unwrap_or_default(),
+                    });
                 }
             }
             
-            if !line_buffer.is_empty() {
-                let _ = tx_stdout.send(line_buffer);
-            }
+            let name = file
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            digests.insert(name, digest);
         }
-    });
+        
+        Ok(digests)
+    })
+    .await
+    .map_err(|e| CreateModelError::File(e.to_string()))?
+}
 
-    let tx_stderr = tx;
-    let stderr_handle = std::thread::spawn(move || {
-        if let Some(mut stderr) = stderr {
-            let mut buffer = [0u8; 1024];
-            let mut line_buffer = String::new();
+/// One line of the `/api/create` stream.
+#[derive(Deserialize)]
+struct CreateStatus {
+    #[serde(default)]
+    status: String,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+/// Creates a model from a Modelfile through the Ollama HTTP API of the given
+/// provider or URL, uploading local weights first. Emits
+/// `ollama-create-progress` for every status line.
+#[tauri::command]
+pub async fn create_ollama_model(
+    window: tauri::Window,
+    db: State<'_, Database>,
+    model_name: String,
+    modelfile_content: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<String, CreateModelError> {
+    let model_name = model_name.trim().to_string();
+    validate_model_name(&model_name).map_err(CreateModelError::InvalidName)?;
+    
+    let base_dir = models_dir(window.app_handle()).map_err(CreateModelError::File)?;
+    let (mut request, local) = create_request(&model_name, &modelfile_content, &base_dir)?;
+    
+    let endpoint = {
+        let conn = db.conn.lock().map_err(|e| CreateModelError::Failed(e.to_string()))?;
+        resolve_endpoint(&conn, db.secrets.as_ref(), provider_id, api_url, None)
+            .map_err(CreateModelError::Failed)?
+    };
+    let base_url = ollama_base_url(&endpoint.api_url).to_string();
+    
+    let progress = |status: &str| {
+        let _ = window.emit(
+            "ollama-create-progress",
+            CreateProgressEvent {
+                model: model_name.clone(),
+                status: status.to_string(),
+                completed: None,
+                total: None,
+                percent: None,
+            },
+        );
+    };
+    
+    if let Some(path) = local.from {
+        progress("enviando arquivos do modelo");
+        request.files = push_blobs(base_url.clone(), endpoint.api_key.clone(), path).await?;
+    }
+    if let Some(path) = local.adapter {
+        progress("enviando adaptador");
+        request.adapters = push_blobs(base_url.clone(), endpoint.api_key.clone(), path).await?;
+    }
+    
+    let client = reqwest::Client::new();
+    let mut http_request = client.post(format!("{}/api/create", base_url)).json(&request);
+    if !endpoint.api_key.is_empty() {
+        http_request = http_request.header("Authorization", format!("Bearer {}", endpoint.api_key));
+    }
+    
+    let mut response = http_request
+        .send()
+        .await
+        .map_err(|e| CreateModelError::Connection(e.to_string()))?;
+    
+    if !response.status().is_success() {
+        return Err(CreateModelError::Ollama {
+            status: response.status().as_u16(),
+            message: response.text().await.unwrap_or_default(),
+        });
+    }
+    
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut succeeded = false;
+    
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| CreateModelError::Connection(e.to_string()))?
+    {
+        buffer.extend_from_slice(&chunk);
+        
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
             
-            while let Ok(n) = stderr.read(&mut buffer) {
-                if n == 0 { break; }
-                
-                let chunk = String::from_utf8_lossy(&buffer[..n]);
-                line_buffer.push_str(&chunk);
-                
-                while let Some(pos) = line_buffer.find(|c| c == '\n' || c == '\r') {
-                    let line = line_buffer[..pos].to_string();
-                    if !line.is_empty() {
-                        let _ = tx_stderr.send(line);
-                    }
-                    line_buffer = line_buffer[pos + 1..].to_string();
-                }
+            let status: CreateStatus = serde_json::from_str(line.trim())
+                .map_err(|e| CreateModelError::Failed(format!("resposta inesperada do Ollama: {}", e)))?;
+            
+            if let Some(error) = status.error {
+                return Err(CreateModelError::Failed(error));
             }
+            succeeded |= status.status == "success";
             
-            if !line_buffer.is_empty() {
-                let _ = tx_stderr.send(line_buffer);
+            let percent = match (status.completed, status.total) {
+                (Some(completed), Some(total)) if total > 0 => {
+                    Some((completed as f64 / total as f64 * 100.0).min(100.0))
+                }
+                _ => None,
+            };
+            
+            let _ = window.emit(
+                "ollama-create-progress",
+                CreateProgressEvent {
+                    model: model_name.clone(),
+                    status: status.status,
+                    completed: status.completed,
+                    total: status.total,
+                    percent,
+                },
+            );
+        }
+    }
+    
+    if !succeeded {
+        return Err(CreateModelError::Interrupted);
+    }
+    
+    Ok(format!("Modelo '{}' criado com sucesso!", model_name))
+}
+
+#[derive(Serialize)]
+struct PullRequest<'a> {
+    model: &'a str,
+    stream: bool,
+}
+
+/// One line of the `/api/pull` stream.
+#[derive(Deserialize)]
+struct PullStatus {
+    #[serde(default)]
+    status: String,
+    digest: Option<String>,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+/// Download speed of the current layer, smoothed so that the ETA does not
+/// jump with every chunk.
+#[derive(Default)]
+struct TransferRate {
+    digest: Option<String>,
+    last: Option<(std::time::Instant, u64)>,
+    bytes_per_second: Option<f64>,
+}
+
+impl TransferRate {
+    const SMOOTHING: f64 = 0.3;
+    
+    fn update(&mut self, digest: &str, completed: u64) -> Option<f64> {
+        self.update_at(std::time::Instant::now(), digest, completed)
+    }
+    
+    fn update_at(&mut self, now: std::time::Instant, digest: &str, completed: u64) -> Option<f64> {
+        if self.digest.as_deref() != Some(digest) {
+            self.digest = Some(digest.to_string());
+            self.last = Some((now, completed));
+            self.bytes_per_second = None;
+            return None;
+        }
+        
+        if let Some((at, bytes)) = self.last {
+            let elapsed = now.duration_since(at).as_secs_f64();
+            if elapsed < 0.5 {
+                return self.bytes_per_second;
             }
+            
+            let sample = completed.saturating_sub(bytes) as f64 / elapsed;
+            self.bytes_per_second = Some(match self.bytes_per_second {
+                Some(rate) => rate + Self::SMOOTHING * (sample - rate),
+                None => sample,
+            });
         }
-    });
+        
+        self.last = Some((now, completed));
+        self.bytes_per_second
+    }
+}
 
-    while let Ok(line) = rx.recv() {
-        let _ = window.emit("ollama-pull-progress", &line);
+/// Seconds left at `bytes_per_second`, once the layer size is known.
+fn eta_seconds(bytes_per_second: Option<f64>, completed: Option<u64>, total: Option<u64>) -> Option<u64> {
+    match (bytes_per_second, completed, total) {
+        (Some(speed), Some(completed), Some(total)) if speed > 0.0 => {
+            Some((total.saturating_sub(completed) as f64 / speed).ceil() as u64)
+        }
+        _ => None,
     }
+}
 
-    let _ = stdout_handle.join();
-    let _ = stderr_handle.join();
+/// Pulls `model` through Ollama's streaming `/api/pull`, calling `on_progress`
+/// for every status line.
+pub async fn pull_model<F>(api_url: &str, api_key: &str, model: &str, mut on_progress: F) -> Result<(), String>
+where
+    F: FnMut(PullProgressEvent),
+{
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/pull", ollama_base_url(api_url));
+    
+    let mut request = client.post(&url).json(&PullRequest { model, stream: true });
+    if !api_key.is_empty() {
+        request = request.header("Authorization", format!("Bearer {}", api_key));
+    }
+    
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| format!("Erro ao conectar com Ollama: {}", e))?;
+    
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama retornou erro {}: {}", status, text));
+    }
+    
+    let mut rate = TransferRate::default();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut succeeded = false;
+    
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Erro ao ler progresso do download: {}", e))?
+    {
+        buffer.extend_from_slice(&chunk);
+        
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            
+            let status: PullStatus = serde_json::from_str(line.trim())
+                .map_err(|e| format!("Erro ao parsear progresso do Ollama: {}", e))?;
+            
+            if let Some(error) = status.error {
+                return Err(error);
+            }
+            succeeded |= status.status == "success";
+            
+            let percent = match (status.completed, status.total) {
+                (Some(completed), Some(total)) if total > 0 => {
+                    Some((completed as f64 / total as f64 * 100.0).min(100.0))
+                }
+                _ => None,
+            };
+            
+            let bytes_per_second = match (&status.digest, status.completed) {
+                (Some(digest), Some(completed)) => rate.update(digest, completed),
+                _ => None,
+            };
+            
+            let eta_seconds = eta_seconds(bytes_per_second, status.completed, status.total);
+            
+            on_progress(PullProgressEvent {
+                model: model.to_string(),
+                status: status.status,
+                digest: status.digest,
+                completed: status.completed,
+                total: status.total,
+                percent,
+                bytes_per_second,
+                eta_seconds,
+            });
+        }
+    }
+    
+    if !succeeded {
+        return Err("O download foi interrompido antes de terminar".to_string());
+    }
+    
+    Ok(())
+}
 
-    let status = child.wait()
-        .map_err(|e| format!("Erro ao aguardar processo: {}", e))?;
+/// Queues a pull through the download manager and waits for it to stop,
+/// so the caller gets the outcome. Progress arrives as `ollama-pull-progress`
+/// events keyed by model name.
+#[tauri::command]
+pub async fn pull_ollama_model(
+    app: tauri::AppHandle,
+    db: State<'_, Database>,
+    downloads: State<'_, Downloads>,
+    model_name: String,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<String, String> {
+    let mut stopped = downloads.subscribe();
+    let mut download = downloads::enqueue(&app, &model_name, provider_id, api_url)?;
+    
+    while !download.state.is_finished() && download.state != DownloadState::Paused {
+        download = match stopped.recv().await {
+            Ok(stopped) if stopped.id == download.id => stopped,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(_)) => {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                downloads::load(&conn, download.id)?
+            }
+            Err(RecvError::Closed) => return Err("O gerenciador de downloads foi encerrado".to_string()),
+        };
+    }
+    
+    match download.state {
+        DownloadState::Completed => Ok(format!("Modelo '{}' baixado com sucesso!", model_name)),
+        DownloadState::Paused => Err(format!("Download de '{}' pausado", model_name)),
+        DownloadState::Cancelled => Err(format!("Download de '{}' cancelado", model_name)),
+        _ => Err(format!(
+            "Erro ao baixar modelo '{}': {}",
+            model_name,
+            download.error.unwrap_or_default()
+        )),
+    }
+}
 
-    if !status.success() {
-        return Err(format!("Erro ao baixar modelo '{}'", model_name));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn accepts_ollama_model_names() {
+        for name in [
+            "llama3",
+            "llama3:8b",
+            "llama3.1:8b-instruct-q4_K_M",
+            "library/llama3",
+            "_private/my-model:v1.2",
+            "registry.example.com:5000/team/model:latest",
+        ] {
+            assert_eq!(validate_model_name(name), Ok(()), "{}", name);
+        }
+    }
+    
+    #[test]
+    fn rejects_invalid_model_names() {
+        assert_eq!(validate_model_name(""), Err("'' não é um nome de modelo válido".to_string()));
+        assert_eq!(validate_model_name("-model"), Err("'-model' não é um nome de modelo válido".to_string()));
+        assert_eq!(validate_model_name("meu modelo"), Err("'meu modelo' não é um nome de modelo válido".to_string()));
+        assert_eq!(validate_model_name("my.team/model"), Err("'my.team' não é um namespace válido".to_string()));
+        assert_eq!(validate_model_name("host:x!/team/model"), Err("'host:x!' não é um host válido".to_string()));
+        assert_eq!(validate_model_name("model:.latest"), Err("'.latest' não é uma tag válida".to_string()));
+        assert_eq!(
+            validate_model_name("a/b/c/d"),
+            Err("use no máximo host/namespace/modelo:tag".to_string())
+        );
+        assert!(validate_model_name(&"m".repeat(81)).is_err());
+    }
+    
+    #[test]
+    fn local_paths_are_relative_to_the_models_folder() {
+        let base = Path::new("/data/LLMpad/models");
+        
+        assert_eq!(local_path("./weights.gguf", base), Some(base.join("./weights.gguf")));
+        assert_eq!(local_path("../adapters/lora", base), Some(base.join("../adapters/lora")));
+        assert_eq!(local_path("/opt/models/weights.gguf", base), Some(PathBuf::from("/opt/models/weights.gguf")));
+        assert_eq!(local_path("llama3:8b", base), None);
+        assert_eq!(local_path("library/llama3", base), None);
+    }
+    
+    #[test]
+    fn create_request_maps_instructions() {
+        let base = Path::new("/data/LLMpad/models");
+        let content = "FROM ./weights.gguf\n\
+                       ADAPTER ../lora\n\
+                       PARAMETER temperature 0.5\n\
+                       PARAMETER stop \"<|end|>\"\n\
+                       PARAMETER stop \"<|user|>\"\n\
+                       SYSTEM Seja breve.\n\
+                       MESSAGE user Oi\n";
+        
+        let (request, local) = create_request("meu-modelo", content, base).unwrap();
+        
+        assert_eq!(local.from, Some(base.join("./weights.gguf")));
+        assert_eq!(local.adapter, Some(base.join("../lora")));
+        assert_eq!(request.from, None);
+        assert_eq!(request.system.as_deref(), Some("Seja breve."));
+        assert_eq!(request.parameters["temperature"], serde_json::json!(0.5));
+        assert_eq!(request.parameters["stop"], serde_json::json!(["<|end|>", "<|user|>"]));
+        assert_eq!(request.messages.len(), 1);
+    }
+    
+    #[test]
+    fn transfer_rate_waits_for_elapsed_time_and_resets_per_layer() {
+        let start = std::time::Instant::now();
+        let at = |millis| start + std::time::Duration::from_millis(millis);
+        let mut rate = TransferRate::default();
+        
+        assert_eq!(rate.update_at(at(0), "sha256:a", 0), None);
+        assert_eq!(rate.update_at(at(0), "sha256:a", 1000), None);
+        assert_eq!(rate.update_at(at(1000), "sha256:a", 1000), Some(1000.0));
+        assert_eq!(rate.update_at(at(1000), "sha256:a", 5000), Some(1000.0));
+        assert_eq!(rate.update_at(at(1200), "sha256:a", 5000), Some(1000.0));
+        assert_eq!(rate.update_at(at(2000), "sha256:a", 3000), Some(1000.0 + 0.3 * (2000.0 - 1000.0)));
+        assert_eq!(rate.update_at(at(3000), "sha256:a", 1000), Some(1300.0 - 0.3 * 1300.0));
+        
+        assert_eq!(rate.update_at(at(3000), "sha256:b", 0), None);
+        assert_eq!(rate.update_at(at(4000), "sha256:b", 500), Some(500.0));
     }
     
-    Ok(format!("Modelo '{}' baixado com sucesso!", model_name))
+    #[test]
+    fn eta_needs_a_known_total_and_a_positive_speed() {
+        assert_eq!(eta_seconds(Some(1000.0), Some(0), Some(2500)), Some(3));
+        assert_eq!(eta_seconds(Some(1000.0), Some(3000), Some(2500)), Some(0));
+        assert_eq!(eta_seconds(Some(1000.0), Some(0), None), None);
+        assert_eq!(eta_seconds(Some(1000.0), None, Some(2500)), None);
+        assert_eq!(eta_seconds(Some(0.0), Some(0), Some(2500)), None);
+        assert_eq!(eta_seconds(None, Some(0), Some(2500)), None);
+    }
+    
+    #[test]
+    fn create_request_rejects_adapters_that_are_not_paths() {
+        let error = create_request("meu-modelo", "FROM llama3\nADAPTER lora\n", Path::new("/models"))
+            .err()
+            .unwrap();
+        
+        assert_eq!(
+            error.to_string(),
+            "Modelfile inválido: linha 2: ADAPTER deve apontar para um arquivo local"
+        );
+    }
 }
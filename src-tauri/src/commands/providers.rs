@@ -0,0 +1,368 @@
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+use crate::database::{provider_from_row, Database, PROVIDER_COLUMNS};
+use crate::models::{Provider, ProviderInput};
+use crate::secrets::{provider_api_key, read_secret, store_secret, SecretStore};
+
+/// Looks up a provider by id, falling back to the default provider when no
+/// id is given. Returns `None` only when no provider is configured at all.
+pub fn resolve_provider(conn: &Connection, provider_id: Option<i64>) -> Result<Option<Provider>, String> {
+    let provider_id = match provider_id {
+        Some(id) => Some(id),
+        None => conn
+            .query_row("SELECT default_provider_id FROM settings WHERE id = 1", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+    
+    let Some(provider_id) = provider_id else {
+        return Ok(None);
+    };
+    
+    conn.query_row(
+        &format!("SELECT {} FROM providers WHERE id = ?1", PROVIDER_COLUMNS),
+        [provider_id],
+        provider_from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .map(Some)
+    .ok_or_else(|| format!("Provedor {} não encontrado", provider_id))
+}
+
+pub struct Endpoint {
+    pub provider_id: Option<i64>,
+    pub api_url: String,
+    pub api_key: String,
+}
+
+impl Endpoint {
+    fn from_provider(secrets: &dyn SecretStore, provider: Provider) -> Result<Self, String> {
+        Ok(Endpoint {
+            provider_id: Some(provider.id),
+            api_key: read_secret(secrets, provider.api_key_ref.as_deref(), &provider.api_key)?,
+            api_url: provider.api_url,
+        })
+    }
+}
+
+/// Picks the endpoint for a request: an explicit provider wins, then an
+/// explicit URL, then the default provider and finally the legacy settings row.
+/// Keys are read from the secret store; an explicit URL without a key reuses
+/// the key of a provider configured for that URL.
+pub fn resolve_endpoint(
+    conn: &Connection,
+    secrets: &dyn SecretStore,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+    api_key: Option<String>,
+) -> Result<Endpoint, String> {
+    let api_key = api_key.filter(|key| !key.is_empty());
+    
+    if provider_id.is_none() {
+        if let Some(api_url) = api_url {
+            if api_key.is_none() {
+                let provider = conn
+                    .query_row(
+                        &format!(
+                            "SELECT {} FROM providers WHERE api_url = ?1
+                             ORDER BY id = (SELECT default_provider_id FROM settings WHERE id = 1) DESC, id
+                             LIMIT 1",
+                            PROVIDER_COLUMNS
+                        ),
+                        [&api_url],
+                        provider_from_row,
+                    )
+                    .optional()
+                    .map_err(|e| e.to_string())?;
+                
+                if let Some(provider) = provider {
+                    return Endpoint::from_provider(secrets, provider);
+                }
+            }
+            
+            return Ok(Endpoint {
+                provider_id: None,
+                api_url,
+                api_key: api_key.unwrap_or_default(),
+            });
+        }
+    }
+    
+    if let Some(provider) = resolve_provider(conn, provider_id)? {
+        return Endpoint::from_provider(secrets, provider);
+    }
+    
+    let (api_url, api_key_ref, plaintext_key): (String, Option<String>, String) = conn
+        .query_row("SELECT api_url, api_key_ref, api_key FROM settings WHERE id = 1", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| e.to_string())?;
+    
+    Ok(Endpoint {
+        provider_id: None,
+        api_url,
+        api_key: match api_key {
+            Some(api_key) => api_key,
+            None => read_secret(secrets, api_key_ref.as_deref(), &plaintext_key)?,
+        },
+    })
+}
+
+/// Stores a provider's API key in the secret store (or removes it when empty)
+/// and records the reference on the provider row.
+pub fn save_provider_key(
+    conn: &Connection,
+    secrets: &dyn SecretStore,
+    provider_id: i64,
+    api_key: &str,
+) -> Result<(), String> {
+    let reference = store_secret(secrets, &provider_api_key(provider_id), api_key)?;
+    
+    conn.execute(
+        "UPDATE providers SET api_key = '', api_key_ref = ?1 WHERE id = ?2",
+        rusqlite::params![reference, provider_id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_providers(db: State<Database>) -> Result<Vec<Provider>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM providers ORDER BY name", PROVIDER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    
+    let providers = stmt
+        .query_map([], provider_from_row)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+    
+    Ok(providers)
+}
+
+#[tauri::command]
+pub fn create_provider(db: State<Database>, provider: ProviderInput) -> Result<Provider, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        "INSERT INTO providers (name, kind, api_url) VALUES (?1, ?2, ?3)",
+        rusqlite::params![provider.name, provider.kind, provider.api_url],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    let id = conn.last_insert_rowid();
+    
+    if let Some(api_key) = provider.api_key {
+        save_provider_key(&conn, db.secrets.as_ref(), id, &api_key)?;
+    }
+    
+    conn.execute(
+        "UPDATE settings SET default_provider_id = ?1 WHERE id = 1 AND default_provider_id IS NULL",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    conn.query_row(
+        &format!("SELECT {} FROM providers WHERE id = ?1", PROVIDER_COLUMNS),
+        [id],
+        provider_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_provider(db: State<Database>, id: i64, provider: ProviderInput) -> Result<Provider, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    let updated = conn
+        .execute(
+            "UPDATE providers SET name = ?1, kind = ?2, api_url = ?3, updated_at = datetime('now') WHERE id = ?4",
+            rusqlite::params![provider.name, provider.kind, provider.api_url, id],
+        )
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Provedor {} não encontrado", id));
+    }
+    
+    if let Some(api_key) = provider.api_key {
+        save_provider_key(&conn, db.secrets.as_ref(), id, &api_key)?;
+    }
+    
+    conn.query_row(
+        &format!("SELECT {} FROM providers WHERE id = ?1", PROVIDER_COLUMNS),
+        [id],
+        provider_from_row,
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_provider(db: State<Database>, id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        "UPDATE settings SET default_provider_id = NULL WHERE default_provider_id = ?1",
+        [id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    conn.execute("UPDATE conversations SET provider_id = NULL WHERE provider_id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute("DELETE FROM providers WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    db.secrets.delete(&provider_api_key(id))?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_default_provider(db: State<Database>, id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    
+    let exists: bool = conn
+        .query_row("SELECT EXISTS (SELECT 1 FROM providers WHERE id = ?1)", [id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    
+    if !exists {
+        return Err(format!("Provedor {} não encontrado", id));
+    }
+    
+    conn.execute("UPDATE settings SET default_provider_id = ?1 WHERE id = 1", [id])
+        .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::MemoryStore;
+    
+    /// A migrated database whose default provider "Padrão" (id 1) points at
+    /// the local Ollama URL with `padrao-key`, plus a second provider.
+    fn setup() -> (Connection, MemoryStore) {
+        let db = Database::in_memory();
+        let conn = db.conn.into_inner().unwrap();
+        let secrets = MemoryStore::default();
+        
+        conn.execute(
+            "INSERT INTO providers (name, api_url) VALUES ('Remoto', 'https://api.example.com/v1')",
+            [],
+        )
+        .unwrap();
+        save_provider_key(&conn, &secrets, 1, "padrao-key").unwrap();
+        save_provider_key(&conn, &secrets, 2, "remoto-key").unwrap();
+        
+        (conn, secrets)
+    }
+    
+    fn resolve(
+        conn: &Connection,
+        secrets: &MemoryStore,
+        provider_id: Option<i64>,
+        api_url: Option<&str>,
+        api_key: Option<&str>,
+    ) -> (Option<i64>, String, String) {
+        let endpoint = resolve_endpoint(
+            conn,
+            secrets,
+            provider_id,
+            api_url.map(str::to_string),
+            api_key.map(str::to_string),
+        )
+        .unwrap();
+        (endpoint.provider_id, endpoint.api_url, endpoint.api_key)
+    }
+    
+    #[test]
+    fn explicit_provider_wins_over_url_and_key() {
+        let (conn, secrets) = setup();
+        
+        assert_eq!(
+            resolve(&conn, &secrets, Some(2), Some("http://outro/v1"), Some("outra-key")),
+            (Some(2), "https://api.example.com/v1".to_string(), "remoto-key".to_string())
+        );
+    }
+    
+    #[test]
+    fn explicit_url_uses_the_given_key() {
+        let (conn, secrets) = setup();
+        
+        assert_eq!(
+            resolve(&conn, &secrets, None, Some("https://api.example.com/v1"), Some("minha-key")),
+            (None, "https://api.example.com/v1".to_string(), "minha-key".to_string())
+        );
+    }
+    
+    #[test]
+    fn explicit_url_without_key_reuses_the_provider_for_that_url() {
+        let (conn, secrets) = setup();
+        
+        assert_eq!(
+            resolve(&conn, &secrets, None, Some("https://api.example.com/v1"), Some("")),
+            (Some(2), "https://api.example.com/v1".to_string(), "remoto-key".to_string())
+        );
+        assert_eq!(
+            resolve(&conn, &secrets, None, Some("http://desconhecido/v1"), None),
+            (None, "http://desconhecido/v1".to_string(), String::new())
+        );
+    }
+    
+    #[test]
+    fn explicit_url_prefers_the_default_provider_among_duplicates() {
+        let (conn, secrets) = setup();
+        conn.execute(
+            "INSERT INTO providers (name, api_url) VALUES ('Remoto 2', 'https://api.example.com/v1')",
+            [],
+        )
+        .unwrap();
+        conn.execute("UPDATE settings SET default_provider_id = 3 WHERE id = 1", []).unwrap();
+        
+        assert_eq!(
+            resolve(&conn, &secrets, None, Some("https://api.example.com/v1"), None).0,
+            Some(3)
+        );
+    }
+    
+    #[test]
+    fn default_provider_is_used_when_nothing_is_given() {
+        let (conn, secrets) = setup();
+        
+        assert_eq!(
+            resolve(&conn, &secrets, None, None, None),
+            (Some(1), "http://localhost:11434/v1".to_string(), "padrao-key".to_string())
+        );
+    }
+    
+    #[test]
+    fn legacy_settings_are_used_without_a_default_provider() {
+        let (conn, secrets) = setup();
+        conn.execute(
+            "UPDATE settings SET default_provider_id = NULL, api_url = 'http://legado/v1', api_key = 'legado-key' WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        
+        assert_eq!(
+            resolve(&conn, &secrets, None, None, None),
+            (None, "http://legado/v1".to_string(), "legado-key".to_string())
+        );
+        assert_eq!(resolve(&conn, &secrets, None, None, Some("pedida")).2, "pedida");
+    }
+    
+    #[test]
+    fn unknown_provider_is_an_error() {
+        let (conn, secrets) = setup();
+        
+        assert_eq!(
+            resolve_endpoint(&conn, &secrets, Some(99), None, None).err(),
+            Some("Provedor 99 não encontrado".to_string())
+        );
+    }
+}
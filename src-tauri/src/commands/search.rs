@@ -0,0 +1,127 @@
+use rusqlite::Connection;
+use tauri::State;
+use crate::database::Database;
+use crate::models::SearchHit;
+
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+/// Turns free text into an FTS5 query: every word becomes a quoted prefix
+/// term, so user input can never be parsed as FTS5 syntax.
+fn build_fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Title hits come first, then message hits. bm25 scores from two FTS5
+/// tables are not comparable, so each source is ranked on its own.
+#[tauri::command]
+pub fn search_messages(
+    db: State<Database>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<SearchHit>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    search(&conn, &query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+}
+
+fn search(conn: &Connection, query: &str, limit: u32) -> Result<Vec<SearchHit>, String> {
+    let Some(fts_query) = build_fts_query(query) else {
+        return Ok(vec![]);
+    };
+    
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.conversation_id, c.title, m.id, m.role,
+                    snippet(messages_fts, 0, '**', '**', '…', 16), bm25(messages_fts), m.created_at, 1
+             FROM messages_fts
+             JOIN messages m ON m.id = messages_fts.rowid
+             JOIN conversations c ON c.id = m.conversation_id
+             WHERE messages_fts MATCH ?1
+             UNION ALL
+             SELECT c.id, c.title, NULL, NULL,
+                    snippet(conversations_fts, 0, '**', '**', '…', 16), bm25(conversations_fts), c.updated_at, 0
+             FROM conversations_fts
+             JOIN conversations c ON c.id = conversations_fts.rowid
+             WHERE conversations_fts MATCH ?1
+             ORDER BY 8, 6
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let hits = stmt
+        .query_map(
+            rusqlite::params![fts_query, limit],
+            |row| {
+                Ok(SearchHit {
+                    conversation_id: row.get(0)?,
+                    conversation_title: row.get(1)?,
+                    message_id: row.get(2)?,
+                    role: row.get(3)?,
+                    snippet: row.get(4)?,
+                    rank: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn fts_syntax_in_the_query_is_quoted() {
+        assert_eq!(build_fts_query("olá mundo").as_deref(), Some("\"olá\"* \"mundo\"*"));
+        assert_eq!(build_fts_query("diz \"oi\"").as_deref(), Some("\"diz\"* \"\"\"oi\"\"\"*"));
+        assert_eq!(build_fts_query("rust*").as_deref(), Some("\"rust*\"*"));
+        assert_eq!(build_fts_query("a AND b OR c").as_deref(), Some("\"a\"* \"AND\"* \"b\"* \"OR\"* \"c\"*"));
+        assert_eq!(build_fts_query("NEAR(a b)").as_deref(), Some("\"NEAR(a\"* \"b)\"*"));
+        assert_eq!(build_fts_query(""), None);
+        assert_eq!(build_fts_query(" \t\n "), None);
+    }
+    
+    #[test]
+    fn hostile_queries_run_without_fts_errors() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        
+        for query in ["\"", "\"\"\"", "*", "a*b", "NEAR(a b, 2)", "a AND", "OR", "NOT x", "col:x", "^a", "(", "-"] {
+            assert!(search(&conn, query, 10).is_ok(), "{}", query);
+        }
+        assert!(search(&conn, "   ", 10).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn title_hits_rank_above_message_hits() {
+        let db = Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        conn.execute("INSERT INTO conversations (id, title) VALUES (1, 'Receitas de bolo')", []).unwrap();
+        conn.execute("INSERT INTO conversations (id, title) VALUES (2, 'Outra conversa com um título bem mais comprido')", []).unwrap();
+        conn.execute(
+            "INSERT INTO messages (conversation_id, role, content) VALUES (2, 'user', 'bolo bolo bolo de cenoura')",
+            [],
+        )
+        .unwrap();
+        
+        let hits = search(&conn, "bolo", 10).unwrap();
+        
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].conversation_id, hits[0].message_id), (1, None));
+        assert_eq!(hits[1].conversation_id, 2);
+        assert!(hits[1].message_id.is_some());
+        assert_eq!(search(&conn, "cenou", 10).unwrap().len(), 1);
+    }
+}
@@ -1,20 +1,41 @@
 use tauri::State;
-use crate::database::Database;
-use crate::models::AppSettings;
+use crate::commands::providers::save_provider_key;
+use crate::database::{parse_generation_params, Database};
+use crate::models::{AppSettings, ContextStrategy};
+use crate::secrets::{store_secret, SETTINGS_API_KEY};
 
 #[tauri::command]
 pub fn get_settings(db: State<Database>) -> Result<AppSettings, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT api_url, api_key, model FROM settings WHERE id = 1")
+        .prepare(
+            "SELECT COALESCE(p.api_url, s.api_url), CASE WHEN p.id IS NULL THEN s.api_key_ref IS NOT NULL OR s.api_key <> ''
+                                                ELSE p.api_key_ref IS NOT NULL OR p.api_key <> '' END,
+                    s.model, s.generation_params, s.system_prompt, s.default_provider_id,
+                    s.context_length, s.context_limits, s.context_strategy, s.auto_title,
+                    s.tools_enabled, s.max_concurrent_downloads
+             FROM settings s LEFT JOIN providers p ON p.id = s.default_provider_id
+             WHERE s.id = 1",
+        )
         .map_err(|e| e.to_string())?;
     
     let settings = stmt
         .query_row([], |row| {
             Ok(AppSettings {
                 api_url: row.get(0)?,
-                api_key: row.get(1)?,
+                api_key: None,
+                has_api_key: row.get(1)?,
                 model: row.get(2)?,
+                generation_params: parse_generation_params(row.get(3)?),
+                system_prompt: row.get(4)?,
+                default_provider_id: row.get(5)?,
+                context_length: row.get(6)?,
+                context_limits: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+                context_strategy: ContextStrategy::parse(&row.get::<_, String>(8)?),
+                auto_title: row.get(9)?,
+                tools_enabled: row.get(10)?,
+                max_concurrent_downloads: row.get(11)?,
+                secrets_error: db.secrets_error.clone(),
             })
         })
         .map_err(|e| e.to_string())?;
@@ -26,11 +47,53 @@ pub fn get_settings(db: State<Database>) -> Result<AppSettings, String> {
 pub fn save_settings(db: State<Database>, settings: AppSettings) -> Result<(), String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     
+    let generation_params = serde_json::to_string(&settings.generation_params)
+        .map_err(|e| e.to_string())?;
+    let context_limits = serde_json::to_string(&settings.context_limits)
+        .map_err(|e| e.to_string())?;
+    
     conn.execute(
-        "UPDATE settings SET api_url = ?1, api_key = ?2, model = ?3 WHERE id = 1",
-        rusqlite::params![settings.api_url, settings.api_key, settings.model],
+        "UPDATE settings SET api_url = ?1, model = ?2, generation_params = ?3, system_prompt = ?4,
+                context_length = ?5, context_limits = ?6, context_strategy = ?7, auto_title = ?8,
+                tools_enabled = ?9, max_concurrent_downloads = ?10
+         WHERE id = 1",
+        rusqlite::params![
+            settings.api_url,
+            settings.model,
+            generation_params,
+            settings.system_prompt,
+            settings.context_length,
+            context_limits,
+            settings.context_strategy.as_str(),
+            settings.auto_title,
+            settings.tools_enabled,
+            settings.max_concurrent_downloads.max(1),
+        ],
     )
     .map_err(|e| e.to_string())?;
     
+    let default_provider_id: Option<i64> = conn
+        .query_row("SELECT default_provider_id FROM settings WHERE id = 1", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    
+    if let Some(provider_id) = default_provider_id {
+        conn.execute(
+            "UPDATE providers SET api_url = ?1, updated_at = datetime('now') WHERE id = ?2",
+            rusqlite::params![settings.api_url, provider_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    if let Some(api_key) = settings.api_key {
+        match default_provider_id {
+            Some(provider_id) => save_provider_key(&conn, db.secrets.as_ref(), provider_id, &api_key)?,
+            None => {
+                let reference = store_secret(db.secrets.as_ref(), SETTINGS_API_KEY, &api_key)?;
+                conn.execute("UPDATE settings SET api_key = '', api_key_ref = ?1 WHERE id = 1", [reference])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    
     Ok(())
 }
@@ -0,0 +1,200 @@
+use rusqlite::Connection;
+use std::collections::HashMap;
+use crate::models::{ChatMessage, ContextStrategy};
+
+pub const DEFAULT_CONTEXT_LENGTH: u32 = 4096;
+
+/// Tokens left free for the reply when the request does not set `max_tokens`.
+const DEFAULT_RESPONSE_RESERVE: u32 = 512;
+
+/// Rough cost of the role markers and separators around each message.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Flat cost assumed for each attached image; the real figure depends on
+/// the model and the image size.
+const IMAGE_TOKENS: usize = 768;
+
+/// Room kept for the summary of dropped turns.
+pub const SUMMARY_RESERVE: u32 = 256;
+
+/// Cheap estimate (about four characters per token) that works for any
+/// model without shipping a tokenizer.
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    message.content.text().chars().count().div_ceil(4)
+        + message.content.image_count() * IMAGE_TOKENS
+        + MESSAGE_OVERHEAD
+}
+
+pub fn estimate_total(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(estimate_tokens).sum()
+}
+
+/// Looks up the limit for `model`, then for its name without the tag
+/// (`llama3` for `llama3:8b`), falling back to `default`.
+pub fn context_length(limits: &HashMap<String, u32>, default: u32, model: &str) -> u32 {
+    limits
+        .get(model)
+        .or_else(|| model.split_once(':').and_then(|(base, _)| limits.get(base)))
+        .copied()
+        .unwrap_or(default)
+}
+
+/// Tokens available for the prompt once the reply has room.
+pub fn prompt_budget(context_length: u32, max_tokens: Option<u32>) -> usize {
+    let reserve = max_tokens
+        .unwrap_or(DEFAULT_RESPONSE_RESERVE)
+        .min(context_length / 2);
+    
+    (context_length - reserve) as usize
+}
+
+/// Number of messages to drop from the start of `history` so that it fits
+/// in `budget` next to `pinned` tokens. The latest message is always kept,
+/// and the kept part never starts with a reply whose question was dropped.
+pub fn overflow(history: &[ChatMessage], pinned: usize, budget: usize) -> usize {
+    let mut total = pinned + estimate_total(history);
+    let mut dropped = 0;
+    
+    while total > budget && dropped + 1 < history.len() {
+        total -= estimate_tokens(&history[dropped]);
+        dropped += 1;
+    }
+    
+    while dropped > 0 && dropped + 1 < history.len() && history[dropped].role != "user" {
+        dropped += 1;
+    }
+    
+    dropped
+}
+
+/// Cuts `text` on a character boundary so it costs at most `tokens`.
+/// Returns the kept part and whether anything was cut.
+pub fn truncate_text(text: &str, tokens: usize) -> (&str, bool) {
+    match text.char_indices().nth(tokens * 4) {
+        Some((index, _)) => (&text[..index], true),
+        None => (text, false),
+    }
+}
+
+/// Keeps the newest messages of `messages` that fit in `budget`.
+pub fn newest_within(messages: &[ChatMessage], budget: usize) -> &[ChatMessage] {
+    let mut total = 0;
+    let mut start = messages.len();
+    
+    while start > 0 {
+        let cost = estimate_tokens(&messages[start - 1]);
+        if total + cost > budget {
+            break;
+        }
+        total += cost;
+        start -= 1;
+    }
+    
+    &messages[start..]
+}
+
+pub struct ContextSettings {
+    pub context_length: u32,
+    pub limits: HashMap<String, u32>,
+    pub strategy: ContextStrategy,
+}
+
+pub fn load_settings(conn: &Connection) -> rusqlite::Result<ContextSettings> {
+    conn.query_row(
+        "SELECT context_length, context_limits, context_strategy FROM settings WHERE id = 1",
+        [],
+        |row| {
+            let limits: String = row.get(1)?;
+            let strategy: String = row.get(2)?;
+            
+            Ok(ContextSettings {
+                context_length: row.get(0)?,
+                limits: serde_json::from_str(&limits).unwrap_or_default(),
+                strategy: ContextStrategy::parse(&strategy),
+            })
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// A message costing `chars / 4` tokens plus the overhead.
+    fn message(role: &str, chars: usize) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: "a".repeat(chars).into(),
+            ..Default::default()
+        }
+    }
+    
+    fn turns(roles: &[&str]) -> Vec<ChatMessage> {
+        roles.iter().map(|role| message(role, 40)).collect()
+    }
+    
+    #[test]
+    fn the_latest_message_is_kept_even_when_it_alone_is_over_budget() {
+        let history = vec![message("user", 40), message("assistant", 40), message("user", 4000)];
+        
+        assert_eq!(overflow(&history, 0, 100), 2);
+        assert_eq!(overflow(&history, 500, 100), 2);
+        assert_eq!(overflow(&history[2..], 0, 0), 0);
+        assert_eq!(overflow(&[], 0, 0), 0);
+    }
+    
+    #[test]
+    fn kept_history_never_starts_with_an_orphaned_reply() {
+        let history = turns(&["user", "assistant", "user", "assistant", "user"]);
+        
+        assert_eq!(overflow(&history, 0, 70), 0);
+        assert_eq!(overflow(&history, 0, 69), 2);
+        assert_eq!(overflow(&history, 14, 70), 2);
+        assert_eq!(overflow(&history, 0, 28), 4);
+        
+        let history = turns(&["user", "assistant", "tool", "assistant", "user"]);
+        assert_eq!(overflow(&history, 0, 50), 4);
+    }
+    
+    #[test]
+    fn the_reply_reserve_is_capped_at_half_the_context() {
+        assert_eq!(prompt_budget(4096, None), 3584);
+        assert_eq!(prompt_budget(4096, Some(1000)), 3096);
+        assert_eq!(prompt_budget(4096, Some(3000)), 2048);
+        assert_eq!(prompt_budget(512, None), 256);
+        assert_eq!(prompt_budget(0, None), 0);
+    }
+    
+    #[test]
+    fn truncation_cuts_on_a_character_boundary() {
+        assert_eq!(truncate_text("çãõé🙂 ação", 1), ("çãõé", true));
+        assert_eq!(truncate_text("çãõé🙂 ação", 2), ("çãõé🙂 aç", true));
+        assert_eq!(truncate_text("çãõé", 1), ("çãõé", false));
+        assert_eq!(truncate_text("🙂🙂🙂", 0), ("", true));
+        assert_eq!(truncate_text("", 0), ("", false));
+    }
+    
+    #[test]
+    fn unknown_models_fall_back_to_the_default_length() {
+        let limits = HashMap::from([("llama3".to_string(), 8192), ("qwen:7b".to_string(), 32768)]);
+        
+        assert_eq!(context_length(&limits, 4096, "llama3"), 8192);
+        assert_eq!(context_length(&limits, 4096, "llama3:8b"), 8192);
+        assert_eq!(context_length(&limits, 4096, "qwen:7b"), 32768);
+        assert_eq!(context_length(&limits, 4096, "qwen:14b"), 4096);
+        assert_eq!(context_length(&limits, 4096, "mistral"), 4096);
+        assert_eq!(context_length(&limits, 2048, ""), 2048);
+        assert_eq!(context_length(&HashMap::new(), DEFAULT_CONTEXT_LENGTH, "llama3"), DEFAULT_CONTEXT_LENGTH);
+    }
+    
+    #[test]
+    fn newest_within_keeps_a_contiguous_tail() {
+        let messages = vec![message("user", 40), message("assistant", 400), message("user", 40)];
+        
+        assert_eq!(newest_within(&messages, 14).len(), 1);
+        assert_eq!(newest_within(&messages, 117).len(), 1);
+        assert_eq!(newest_within(&messages, 118).len(), 2);
+        assert_eq!(newest_within(&messages, 1000).len(), 3);
+        assert!(newest_within(&messages, 13).is_empty());
+    }
+}
@@ -1,12 +1,146 @@
-use rusqlite::{Connection, Result as SqliteResult};
+use rusqlite::{Connection, Result as SqliteResult, Row};
 use std::sync::Mutex;
+use crate::migrations;
+use crate::secrets::{self, SecretStore};
+use crate::attachments;
+use crate::models::{
+    Attachment, Conversation, Download, DownloadState, GenerationParams, KnowledgeBase, McpServer, McpTransport,
+    Message, Provider,
+};
+
+pub const CONVERSATION_COLUMNS: &str = "id, title, created_at, updated_at, generation_params, system_prompt, model, api_url, provider_id, current_message_id";
+
+pub fn conversation_from_row(row: &Row) -> SqliteResult<Conversation> {
+    Ok(Conversation {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        generation_params: parse_json_column(row.get(4)?),
+        system_prompt: row.get(5)?,
+        model: row.get(6)?,
+        api_url: row.get(7)?,
+        provider_id: row.get(8)?,
+        current_message_id: row.get(9)?,
+    })
+}
+
+pub fn parse_generation_params(value: Option<String>) -> GenerationParams {
+    parse_json_column(value).unwrap_or_default()
+}
+
+fn parse_json_column<T: serde::de::DeserializeOwned>(value: Option<String>) -> Option<T> {
+    value.and_then(|s| serde_json::from_str(&s).ok())
+}
+
+pub const PROVIDER_COLUMNS: &str = "id, name, kind, api_url, api_key_ref, created_at, updated_at, api_key";
+
+pub fn provider_from_row(row: &Row) -> SqliteResult<Provider> {
+    Ok(Provider {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        kind: row.get(2)?,
+        api_url: row.get(3)?,
+        api_key_ref: row.get(4)?,
+        api_key: row.get(7)?,
+        has_api_key: row.get::<_, Option<String>>(4)?.is_some() || !row.get::<_, String>(7)?.is_empty(),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+pub const MESSAGE_COLUMNS: &str = "id, conversation_id, role, content, created_at, stopped, model, parent_message_id";
+
+pub fn message_from_row(row: &Row) -> SqliteResult<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        created_at: row.get(4)?,
+        stopped: row.get(5)?,
+        model: row.get(6)?,
+        parent_message_id: row.get(7)?,
+    })
+}
+
+pub const ATTACHMENT_COLUMNS: &str = "id, message_id, kind, file_name, mime_type, size, created_at";
+
+pub fn attachment_from_row(row: &Row) -> SqliteResult<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        message_id: row.get(1)?,
+        kind: row.get(2)?,
+        file_name: row.get(3)?,
+        mime_type: row.get(4)?,
+        size: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+pub const KNOWLEDGE_BASE_COLUMNS: &str = "id, name, source_path, embedding_model, provider_id, api_url, \
+    (SELECT COUNT(*) FROM knowledge_chunks WHERE knowledge_base_id = knowledge_bases.id), indexed_at, created_at, updated_at";
+
+pub fn knowledge_base_from_row(row: &Row) -> SqliteResult<KnowledgeBase> {
+    Ok(KnowledgeBase {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        source_path: row.get(2)?,
+        embedding_model: row.get(3)?,
+        provider_id: row.get(4)?,
+        api_url: row.get(5)?,
+        chunk_count: row.get(6)?,
+        indexed_at: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+pub const MCP_SERVER_COLUMNS: &str = "id, name, transport, command, args, env, url, enabled, require_approval, created_at, updated_at";
+
+pub fn mcp_server_from_row(row: &Row) -> SqliteResult<McpServer> {
+    Ok(McpServer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        transport: McpTransport::parse(&row.get::<_, String>(2)?),
+        command: row.get(3)?,
+        args: parse_json_column(row.get(4)?).unwrap_or_default(),
+        env: parse_json_column(row.get(5)?).unwrap_or_default(),
+        url: row.get(6)?,
+        enabled: row.get(7)?,
+        require_approval: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+pub const DOWNLOAD_COLUMNS: &str = "id, model, provider_id, api_url, state, status, completed, total, error, created_at, updated_at";
+
+pub fn download_from_row(row: &Row) -> SqliteResult<Download> {
+    Ok(Download {
+        id: row.get(0)?,
+        model: row.get(1)?,
+        provider_id: row.get(2)?,
+        api_url: row.get(3)?,
+        state: DownloadState::parse(&row.get::<_, String>(4)?),
+        status: row.get(5)?,
+        completed: row.get(6)?,
+        total: row.get(7)?,
+        error: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
 
 pub struct Database {
     pub conn: Mutex<Connection>,
+    pub secrets: Box<dyn SecretStore>,
+    /// Why plaintext API keys could not be moved to `secrets` at startup.
+    pub secrets_error: Option<String>,
 }
 
 impl Database {
-    pub fn new(app_handle: &tauri::AppHandle) -> SqliteResult<Self> {
+    pub fn new(app_handle: &tauri::AppHandle) -> Result<Self, String> {
         use tauri::Manager;
         
         let app_dir = app_handle
@@ -17,47 +151,39 @@ impl Database {
         std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
         
         let db_path = app_dir.join("llmpad.db");
-        let conn = Connection::open(db_path)?;
+        let mut conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
         
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS conversations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                title TEXT NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-            )",
-            [],
-        )?;
+        migrations::run(&mut conn, &db_path)?;
         
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                conversation_id INTEGER NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
-        
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS settings (
-                id INTEGER PRIMARY KEY CHECK (id = 1),
-                api_url TEXT NOT NULL DEFAULT 'http://localhost:11434/v1',
-                api_key TEXT NOT NULL DEFAULT '',
-                model TEXT NOT NULL DEFAULT 'llama3.2'
-            )",
-            [],
-        )?;
-        
-        conn.execute(
-            "INSERT OR IGNORE INTO settings (id, api_url, api_key, model) VALUES (1, 'http://localhost:11434/v1', '', 'llama3.2')",
-            [],
-        )?;
+        let secrets = secrets::open(&app_dir);
+        // Keys that could not be moved stay in their plaintext columns, are
+        // still used, and are retried on the next start; the settings screen
+        // shows why.
+        let secrets_error = secrets::migrate_plaintext_keys(&conn, secrets.as_ref())
+            .and_then(|()| secrets::scrub_backups(&db_path))
+            .err()
+            .map(|e| format!("Erro ao mover chaves de API para o armazenamento de segredos: {}", e));
+        attachments::discard_stale(&conn).map_err(|e| e.to_string())?;
         
         Ok(Database {
             conn: Mutex::new(conn),
+            secrets,
+            secrets_error,
         })
     }
 }
+
+#[cfg(test)]
+impl Database {
+    /// A fully migrated in-memory database with secrets kept in memory.
+    pub fn in_memory() -> Database {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::run(&mut conn, std::path::Path::new("llmpad.db")).unwrap();
+        
+        Database {
+            conn: Mutex::new(conn),
+            secrets: Box::new(secrets::MemoryStore::default()),
+            secrets_error: None,
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::io::{Cursor, Read};
+
+pub const MAX_DOCUMENT_SIZE: usize = 20 * 1024 * 1024;
+
+/// Cap on the bytes inflated from a DOCX or from all of a PDF's streams, so a
+/// small compressed file cannot expand without bound.
+const MAX_DECOMPRESSED_SIZE: u64 = 100 * 1024 * 1024;
+
+const DOCX_MIME: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+/// Text extracted from an attached document, with the MIME type it was
+/// recognised as.
+pub struct ExtractedDocument {
+    pub mime_type: &'static str,
+    pub text: String,
+}
+
+/// Extracts the text of a PDF, a DOCX or any UTF-8 text file (plain text,
+/// Markdown, source code). The format comes from the content; the extension
+/// only picks the MIME type of text files.
+pub fn extract_text(file_name: &str, bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    if bytes.len() > MAX_DOCUMENT_SIZE {
+        return Err(format!(
+            "Documento muito grande ({} MB, máximo {} MB)",
+            bytes.len().div_ceil(1024 * 1024),
+            MAX_DOCUMENT_SIZE / (1024 * 1024)
+        ));
+    }
+    
+    let (mime_type, text) = if bytes.starts_with(b"%PDF-") {
+        ("application/pdf", extract_pdf(bytes)?)
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        (DOCX_MIME, extract_docx(bytes)?)
+    } else {
+        let text = std::str::from_utf8(bytes)
+            .ok()
+            .filter(|text| !text.contains('\0'))
+            .ok_or_else(|| "Formato de arquivo não suportado (use texto, Markdown, código, PDF ou DOCX)".to_string())?;
+        (text_mime(file_name), text.trim_start_matches('\u{feff}').to_string())
+    };
+    
+    if text.trim().is_empty() {
+        return Err("Nenhum texto encontrado no documento".to_string());
+    }
+    
+    Ok(ExtractedDocument { mime_type, text })
+}
+
+fn text_mime(file_name: &str) -> &'static str {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    
+    match extension.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        _ => "text/plain",
+    }
+}
+
+fn decompressed_too_large() -> String {
+    format!(
+        "Documento muito grande depois de descompactado (máximo {} MB)",
+        MAX_DECOMPRESSED_SIZE / (1024 * 1024)
+    )
+}
+
+fn extract_docx(bytes: &[u8]) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| format!("Erro ao abrir DOCX: {}", e))?;
+    
+    let mut xml = Vec::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|_| "Arquivo DOCX inválido: word/document.xml não encontrado".to_string())?
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut xml)
+        .map_err(|e| format!("Erro ao ler DOCX: {}", e))?;
+    
+    if xml.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(decompressed_too_large());
+    }
+    
+    let xml = String::from_utf8(xml).map_err(|e| format!("Erro ao ler DOCX: {}", e))?;
+    let document = roxmltree::Document::parse(&xml).map_err(|e| format!("Erro ao ler DOCX: {}", e))?;
+    
+    let mut text = String::new();
+    for node in document.descendants() {
+        match node.tag_name().name() {
+            "t" => text.push_str(node.text().unwrap_or_default()),
+            "tab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            "p" if !text.is_empty() && !text.ends_with('\n') => text.push('\n'),
+            _ => {}
+        }
+    }
+    
+    Ok(text)
+}
+
+/// Best-effort PDF text extraction: inflates the content streams and reads
+/// the strings shown by the text operators. Scanned PDFs and fonts without a
+/// simple encoding yield little or no text.
+fn extract_pdf(bytes: &[u8]) -> Result<String, String> {
+    let mut text = String::new();
+    let mut position = 0;
+    let mut inflated_total = 0;
+    
+    while let Some(start) = find(bytes, b"stream", position) {
+        let Some(end) = find(bytes, b"endstream", start) else {
+            break;
+        };
+        position = end + b"endstream".len();
+        
+        if start >= 3 && &bytes[start - 3..start] == b"end" {
+            continue;
+        }
+        
+        let dictionary_start = rfind(bytes, b"<<", start).unwrap_or(0);
+        let dictionary = String::from_utf8_lossy(&bytes[dictionary_start..start]);
+        if dictionary.contains("/Image") || dictionary.contains("/FontFile") || dictionary.contains("/XRef") {
+            continue;
+        }
+        
+        let mut data_start = start + b"stream".len();
+        while data_start < end && matches!(bytes[data_start], b'\r' | b'\n') {
+            data_start += 1;
+        }
+        let data = &bytes[data_start..end];
+        
+        let content = if dictionary.contains("/FlateDecode") {
+            let mut inflated = Vec::new();
+            let read = flate2::read::ZlibDecoder::new(data)
+                .take(MAX_DECOMPRESSED_SIZE - inflated_total + 1)
+                .read_to_end(&mut inflated);
+            
+            inflated_total += inflated.len() as u64;
+            if inflated_total > MAX_DECOMPRESSED_SIZE {
+                return Err(decompressed_too_large());
+            }
+            
+            if read.is_err() && inflated.is_empty() {
+                continue;
+            }
+            inflated
+        } else if dictionary.contains("/Filter") {
+            continue;
+        } else {
+            data.to_vec()
+        };
+        
+        text.push_str(&content_stream_text(&content));
+    }
+    
+    if text.trim().is_empty() {
+        return Err("Não foi possível extrair texto do PDF (ele pode conter apenas imagens)".to_string());
+    }
+    
+    Ok(text
+        .trim()
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| index + from)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8], before: usize) -> Option<usize> {
+    haystack[..before]
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+/// Walks the operators of a page content stream, keeping the text shown by
+/// `Tj`, `TJ`, `'` and `"` and turning line moves into newlines.
+fn content_stream_text(content: &[u8]) -> String {
+    let mut text = String::new();
+    let mut strings: Vec<String> = Vec::new();
+    let mut numbers: Vec<f64> = Vec::new();
+    let mut in_array = false;
+    let mut i = 0;
+    
+    while i < content.len() {
+        match content[i] {
+            b'(' => {
+                let (string, next) = literal_string(content, i + 1);
+                strings.push(string);
+                i = next;
+            }
+            b'<' if content.get(i + 1) != Some(&b'<') => {
+                let end = find(content, b">", i).unwrap_or(content.len());
+                strings.push(hex_string(&content[i + 1..end]));
+                i = end + 1;
+            }
+            b'[' => {
+                in_array = true;
+                i += 1;
+            }
+            b']' => {
+                in_array = false;
+                i += 1;
+            }
+            b'%' => {
+                while i < content.len() && !matches!(content[i], b'\r' | b'\n') {
+                    i += 1;
+                }
+            }
+            b'/' => {
+                i += 1;
+                while i < content.len() && !content[i].is_ascii_whitespace() && !b"()<>[]/%".contains(&content[i]) {
+                    i += 1;
+                }
+            }
+            b'<' => i += 2,
+            b'>' => i += 1,
+            byte if byte.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < content.len()
+                    && !content[i].is_ascii_whitespace()
+                    && !b"()<>[]/%".contains(&content[i])
+                {
+                    i += 1;
+                }
+                if i == start {
+                    i += 1;
+                    continue;
+                }
+                
+                let token = String::from_utf8_lossy(&content[start..i]);
+                if let Ok(number) = token.parse::<f64>() {
+                    if in_array && number < -200.0 {
+                        strings.push(" ".to_string());
+                    }
+                    numbers.push(number);
+                    continue;
+                }
+                
+                match token.as_ref() {
+                    "Tj" | "TJ" => text.push_str(&strings.concat()),
+                    "'" | "\"" => {
+                        text.push('\n');
+                        text.push_str(&strings.concat());
+                    }
+                    "T*" | "ET" => text.push('\n'),
+                    "Td" | "TD" if numbers.last().is_some_and(|y| *y != 0.0) => text.push('\n'),
+                    _ => {}
+                }
+                
+                strings.clear();
+                numbers.clear();
+            }
+        }
+    }
+    
+    text
+}
+
+fn literal_string(content: &[u8], mut i: usize) -> (String, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 0;
+    
+    while i < content.len() {
+        match content[i] {
+            b'\\' if i + 1 < content.len() => {
+                i += 1;
+                match content[i] {
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'b' | b'f' => {}
+                    b'\r' | b'\n' => {}
+                    digit @ b'0'..=b'7' => {
+                        let mut value = u32::from(digit - b'0');
+                        let mut count = 1;
+                        while count < 3 && i + 1 < content.len() && (b'0'..=b'7').contains(&content[i + 1]) {
+                            i += 1;
+                            value = value * 8 + u32::from(content[i] - b'0');
+                            count += 1;
+                        }
+                        bytes.push(value as u8);
+                    }
+                    other => bytes.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(b'(');
+            }
+            b')' if depth == 0 => return (decode_pdf_bytes(&bytes), i + 1),
+            b')' => {
+                depth -= 1;
+                bytes.push(b')');
+            }
+            byte => bytes.push(byte),
+        }
+        i += 1;
+    }
+    
+    (decode_pdf_bytes(&bytes), i)
+}
+
+fn hex_string(hex: &[u8]) -> String {
+    let digits: Vec<u8> = hex
+        .iter()
+        .filter_map(|byte| (*byte as char).to_digit(16).map(|digit| digit as u8))
+        .collect();
+    let bytes: Vec<u8> = digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect();
+    
+    decode_pdf_bytes(&bytes)
+}
+
+/// UTF-16 strings carry a byte order mark; everything else is read as
+/// Latin-1, which matches the usual simple font encodings for ASCII and
+/// Western European text.
+fn decode_pdf_bytes(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xfe, 0xff]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    
+    bytes
+        .iter()
+        .filter(|byte| **byte >= 0x20 || matches!(**byte, b'\t' | b'\n'))
+        .map(|byte| *byte as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+    
+    /// A one-page PDF whose content stream holds `stream` as is, with
+    /// `filter` added to the stream dictionary.
+    fn pdf(filter: &str, stream: &[u8]) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n".to_vec();
+        pdf.extend_from_slice(b"2 0 obj\n<< /Type /Pages /Kids [3 0 R] /Count 1 >>\nendobj\n");
+        pdf.extend_from_slice(b"3 0 obj\n<< /Type /Page /Parent 2 0 R /Contents 4 0 R >>\nendobj\n");
+        pdf.extend_from_slice(format!("4 0 obj\n<< /Length {} {} >>\nstream\n", stream.len(), filter).as_bytes());
+        pdf.extend_from_slice(stream);
+        pdf.extend_from_slice(b"\nendstream\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        pdf
+    }
+    
+    fn docx(document_xml: &[u8]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        writer.start_file("[Content_Types].xml", options).unwrap();
+        writer.write_all(b"<?xml version=\"1.0\"?><Types/>").unwrap();
+        writer.start_file("word/document.xml", options).unwrap();
+        writer.write_all(document_xml).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+    
+    #[test]
+    fn pdf_text_comes_from_a_flate_content_stream() {
+        let content = b"BT /F1 12 Tf 72 720 Td (Ol\\341, mundo) Tj 0 -14 Td [(Segunda) -250 (linha)] TJ ET";
+        let extracted = extract_text("doc.pdf", &pdf("/Filter /FlateDecode", &deflate(content))).unwrap();
+        
+        assert_eq!(extracted.mime_type, "application/pdf");
+        assert_eq!(extracted.text, "Olá, mundo\nSegunda linha");
+    }
+    
+    #[test]
+    fn pdf_with_hex_and_utf16_strings() {
+        let content = b"BT <FEFF00C70061006F> Tj T* <4F69> Tj ET";
+        
+        assert_eq!(extract_text("doc.pdf", &pdf("", content)).unwrap().text, "Çao\nOi");
+    }
+    
+    #[test]
+    fn docx_text_keeps_paragraphs_and_tabs() {
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\"><w:body>\
+            <w:p><w:r><w:t>Relatório</w:t><w:tab/><w:t>2024</w:t></w:r></w:p>\
+            <w:p><w:r><w:t xml:space=\"preserve\">Ações </w:t></w:r><w:r><w:t>e metas</w:t><w:br/><w:t>fim</w:t></w:r></w:p>\
+            </w:body></w:document>";
+        let extracted = extract_text("relatorio.docx", &docx(xml.as_bytes())).unwrap();
+        
+        assert_eq!(extracted.mime_type, DOCX_MIME);
+        assert_eq!(extracted.text, "Relatório\t2024\nAções e metas\nfim");
+    }
+    
+    #[test]
+    fn truncated_or_corrupt_files_are_errors() {
+        let whole = docx(b"<document><p><t>Oi</t></p></document>");
+        assert!(extract_text("a.docx", &whole[..whole.len() / 2]).is_err());
+        assert!(extract_text("a.docx", b"PK\x03\x04lixo").is_err());
+        assert!(extract_text("a.docx", &docx(b"<document><p><t>Oi</t>")).is_err());
+        
+        let whole = pdf("/Filter /FlateDecode", &deflate(b"BT (Oi) Tj ET"));
+        assert!(extract_text("a.pdf", &whole[..whole.len() / 2]).is_err());
+        assert!(extract_text("a.pdf", &pdf("/Filter /FlateDecode", b"not zlib at all")).is_err());
+        assert!(extract_text("a.pdf", b"%PDF-1.4\nstream").is_err());
+        assert!(extract_text("a.pdf", &pdf("", b"BT (unterminated")).is_err());
+        
+        assert!(extract_text("a.bin", &[0xff, 0xfe, 0x00, 0x41]).is_err());
+        assert!(extract_text("a.txt", b"   \n").is_err());
+    }
+    
+    #[test]
+    fn text_files_are_read_as_utf8() {
+        let extracted = extract_text("notas.md", "\u{feff}# Título\n\nçãõ 🙂".as_bytes()).unwrap();
+        
+        assert_eq!(extracted.mime_type, "text/markdown");
+        assert_eq!(extracted.text, "# Título\n\nçãõ 🙂");
+    }
+    
+    #[test]
+    fn streams_inflating_past_the_cap_are_refused() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        let chunk = vec![b' '; 1024 * 1024];
+        for _ in 0..=MAX_DECOMPRESSED_SIZE / chunk.len() as u64 {
+            encoder.write_all(&chunk).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < MAX_DOCUMENT_SIZE);
+        
+        let error = extract_text("a.pdf", &pdf("/Filter /FlateDecode", &bomb)).err().unwrap();
+        assert_eq!(error, decompressed_too_large());
+        
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(true);
+        writer.start_file("word/document.xml", options).unwrap();
+        for _ in 0..=MAX_DECOMPRESSED_SIZE / chunk.len() as u64 {
+            writer.write_all(&chunk).unwrap();
+        }
+        let bomb = writer.finish().unwrap().into_inner();
+        
+        let error = extract_text("a.docx", &bomb).err().unwrap();
+        assert_eq!(error, decompressed_too_large());
+    }
+}
@@ -0,0 +1,316 @@
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{broadcast, oneshot};
+use crate::commands::ollama::pull_model;
+use crate::commands::providers::resolve_endpoint;
+use crate::database::{download_from_row, Database, DOWNLOAD_COLUMNS};
+use crate::models::{Download, DownloadState};
+
+/// Minimum interval between progress writes to the database.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How a running download is stopped. Ollama keeps the layers it already
+/// fetched, so a paused download continues where it left off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopAction {
+    Pause,
+    Cancel,
+}
+
+/// Running downloads, by id, and a channel announcing the ones that stopped.
+pub struct Downloads {
+    active: Mutex<HashMap<i64, oneshot::Sender<StopAction>>>,
+    stopped: broadcast::Sender<Download>,
+}
+
+impl Default for Downloads {
+    fn default() -> Self {
+        Downloads {
+            active: Mutex::new(HashMap::new()),
+            stopped: broadcast::channel(64).0,
+        }
+    }
+}
+
+impl Downloads {
+    /// Stops a running download. Returns false when it is not running.
+    pub fn stop(&self, id: i64, action: StopAction) -> Result<bool, String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        
+        match active.remove(&id) {
+            Some(tx) => Ok(tx.send(action).is_ok()),
+            None => Ok(false),
+        }
+    }
+    
+    /// Downloads that completed, failed, were cancelled or paused from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Download> {
+        self.stopped.subscribe()
+    }
+}
+
+pub fn load(conn: &Connection, id: i64) -> Result<Download, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM downloads WHERE id = ?1", DOWNLOAD_COLUMNS),
+        [id],
+        download_from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Download {} não encontrado", id))
+}
+
+pub fn set_state(conn: &Connection, id: i64, state: DownloadState, error: Option<&str>) -> Result<Download, String> {
+    conn.execute(
+        "UPDATE downloads SET state = ?1, error = ?2, updated_at = datetime('now') WHERE id = ?3",
+        rusqlite::params![state.as_str(), error, id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    load(conn, id)
+}
+
+/// Emits `download-updated`, and announces downloads that stopped to
+/// `Downloads::subscribe`.
+pub fn notify(app: &AppHandle, download: &Download) {
+    let _ = app.emit("download-updated", download);
+    
+    if download.state.is_finished() || download.state == DownloadState::Paused {
+        let _ = app.state::<Downloads>().stopped.send(download.clone());
+    }
+}
+
+/// Adds a pull to the queue, or requeues the matching one when the model is
+/// already queued, downloading or paused for the same endpoint.
+pub fn enqueue(
+    app: &AppHandle,
+    model: &str,
+    provider_id: Option<i64>,
+    api_url: Option<String>,
+) -> Result<Download, String> {
+    let model = model.trim();
+    if model.is_empty() {
+        return Err("Informe o nome do modelo".to_string());
+    }
+    
+    let download = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let endpoint = resolve_endpoint(&conn, db.secrets.as_ref(), provider_id, api_url, None)?;
+        let api_url = endpoint.provider_id.is_none().then_some(endpoint.api_url);
+        
+        let existing = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM downloads
+                     WHERE model = ?1 AND provider_id IS ?2 AND api_url IS ?3
+                       AND state IN ('queued', 'downloading', 'paused')
+                     ORDER BY id DESC LIMIT 1",
+                    DOWNLOAD_COLUMNS
+                ),
+                rusqlite::params![model, endpoint.provider_id, api_url],
+                download_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        
+        match existing {
+            Some(download) if download.state == DownloadState::Paused => {
+                set_state(&conn, download.id, DownloadState::Queued, None)?
+            }
+            Some(download) => return Ok(download),
+            None => {
+                conn.execute(
+                    "INSERT INTO downloads (model, provider_id, api_url) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![model, endpoint.provider_id, api_url],
+                )
+                .map_err(|e| e.to_string())?;
+                load(&conn, conn.last_insert_rowid())?
+            }
+        }
+    };
+    
+    notify(app, &download);
+    pump(app)?;
+    
+    Ok(download)
+}
+
+/// Requeues the downloads interrupted by the last shutdown and starts the
+/// queue. Called once at startup; a queue that cannot be read now is started
+/// again by the next enqueue, resume or finished download.
+pub fn resume_interrupted(app: &AppHandle) {
+    {
+        let db = app.state::<Database>();
+        let Ok(conn) = db.conn.lock() else {
+            return;
+        };
+        let _ = conn.execute(
+            "UPDATE downloads SET state = 'queued', updated_at = datetime('now') WHERE state = 'downloading'",
+            [],
+        );
+    }
+    
+    let _ = pump(app);
+}
+
+/// Starts queued downloads, oldest first, while fewer than
+/// `max_concurrent_downloads` are running. A download that cannot be started
+/// is marked as failed; an error is returned when the queue cannot be read.
+pub fn pump(app: &AppHandle) -> Result<(), String> {
+    for download in start_queued(app)? {
+        notify(app, &download);
+    }
+    
+    Ok(())
+}
+
+/// Marks a download as failed. When that cannot be saved either, the failed
+/// download is still returned so it can be announced.
+fn fail(conn: &Connection, download: Download, error: String) -> Download {
+    set_state(conn, download.id, DownloadState::Failed, Some(&error)).unwrap_or(Download {
+        state: DownloadState::Failed,
+        error: Some(error),
+        ..download
+    })
+}
+
+fn start_queued(app: &AppHandle) -> Result<Vec<Download>, String> {
+    let db = app.state::<Database>();
+    let downloads = app.state::<Downloads>();
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let mut active = downloads.active.lock().map_err(|e| e.to_string())?;
+    
+    let limit: u32 = conn
+        .query_row("SELECT max_concurrent_downloads FROM settings WHERE id = 1", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let free = (limit.max(1) as usize).saturating_sub(active.len());
+    if free == 0 {
+        return Ok(vec![]);
+    }
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM downloads WHERE state = 'queued' ORDER BY id LIMIT ?1",
+            DOWNLOAD_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let queued = stmt
+        .query_map([free as i64], download_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    let mut started = Vec::new();
+    for download in queued {
+        let download = match set_state(&conn, download.id, DownloadState::Downloading, None) {
+            Ok(download) => download,
+            Err(e) => {
+                started.push(fail(&conn, download, format!("Erro ao iniciar download: {}", e)));
+                continue;
+            }
+        };
+        let (tx, rx) = oneshot::channel();
+        active.insert(download.id, tx);
+        tauri::async_runtime::spawn(run(app.clone(), download.clone(), rx));
+        started.push(download);
+    }
+    
+    Ok(started)
+}
+
+async fn run(app: AppHandle, download: Download, mut stop: oneshot::Receiver<StopAction>) {
+    let (state, error) = tokio::select! {
+        result = pull(&app, &download) => match result {
+            Ok(()) => (DownloadState::Completed, None),
+            Err(e) => (DownloadState::Failed, Some(e)),
+        },
+        action = &mut stop => match action {
+            Ok(StopAction::Pause) => (DownloadState::Paused, None),
+            _ => (DownloadState::Cancelled, None),
+        },
+    };
+    
+    if let Ok(mut active) = app.state::<Downloads>().active.lock() {
+        active.remove(&download.id);
+    }
+    
+    let finished = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().map_err(|e| e.to_string());
+        conn.and_then(|conn| set_state(&conn, download.id, state, error.as_deref()))
+    };
+    
+    // When the final state cannot be saved, the UI still hears about it, with
+    // the reason; the row is requeued as interrupted on the next start.
+    let finished = finished.unwrap_or_else(|e| Download {
+        state,
+        error: Some(match error {
+            Some(error) => format!("{} (não foi possível salvar o estado: {})", error, e),
+            None => format!("Não foi possível salvar o estado do download: {}", e),
+        }),
+        ..download
+    });
+    notify(&app, &finished);
+    
+    // Like at startup, a queue that cannot be read now is started again by
+    // the next enqueue or resume.
+    let _ = pump(&app);
+}
+
+/// Pulls the model, emitting `ollama-pull-progress` for every status line and
+/// saving the overall progress at most once per `PROGRESS_INTERVAL`.
+async fn pull(app: &AppHandle, download: &Download) -> Result<(), String> {
+    let endpoint = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        resolve_endpoint(
+            &conn,
+            db.secrets.as_ref(),
+            download.provider_id,
+            download.api_url.clone(),
+            None,
+        )?
+    };
+    
+    let mut layers: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut saved_at: Option<Instant> = None;
+    
+    pull_model(&endpoint.api_url, &endpoint.api_key, &download.model, |progress| {
+        if let (Some(digest), Some(total)) = (&progress.digest, progress.total) {
+            layers.insert(digest.clone(), (progress.completed.unwrap_or(0), total));
+        }
+        
+        let _ = app.emit("ollama-pull-progress", &progress);
+        
+        if saved_at.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        saved_at = Some(Instant::now());
+        
+        let completed: u64 = layers.values().map(|(completed, _)| completed).sum();
+        let total: u64 = layers.values().map(|(_, total)| total).sum();
+        
+        let db = app.state::<Database>();
+        let Ok(conn) = db.conn.lock() else {
+            return;
+        };
+        let saved = conn
+            .execute(
+                "UPDATE downloads SET status = ?1, completed = ?2, total = ?3, updated_at = datetime('now')
+                 WHERE id = ?4",
+                rusqlite::params![progress.status, completed as i64, total as i64, download.id],
+            )
+            .map_err(|e| e.to_string())
+            .and_then(|_| load(&conn, download.id));
+        drop(conn);
+        
+        if let Ok(saved) = saved {
+            notify(app, &saved);
+        }
+    })
+    .await
+}
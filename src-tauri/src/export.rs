@@ -0,0 +1,208 @@
+use crate::models::{ExportDocument, ExportFormat, ExportedConversation};
+
+pub const EXPORT_FORMAT_NAME: &str = "llmpad";
+/// Version 2 added the message tree (`parent_message_id`, `current_message_id`).
+pub const EXPORT_FORMAT_VERSION: u32 = 2;
+
+pub fn render(conversations: Vec<ExportedConversation>, format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Markdown => Ok(to_markdown(&conversations)),
+        ExportFormat::Json => to_json(conversations),
+        ExportFormat::Html => Ok(to_html(&conversations)),
+    }
+}
+
+pub fn to_json(conversations: Vec<ExportedConversation>) -> Result<String, String> {
+    let document = ExportDocument {
+        format: EXPORT_FORMAT_NAME.to_string(),
+        version: EXPORT_FORMAT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        conversations,
+    };
+    
+    serde_json::to_string_pretty(&document).map_err(|e| e.to_string())
+}
+
+pub fn to_markdown(conversations: &[ExportedConversation]) -> String {
+    let mut out = String::new();
+    
+    for (i, exported) in conversations.iter().enumerate() {
+        if i > 0 {
+            out.push_str("\n---\n\n");
+        }
+        
+        let conversation = &exported.conversation;
+        out.push_str(&format!("# {}\n\n", conversation.title));
+        out.push_str(&format!("- Criada em: {}\n", conversation.created_at));
+        out.push_str(&format!("- Atualizada em: {}\n", conversation.updated_at));
+        if let Some(model) = &conversation.model {
+            out.push_str(&format!("- Modelo: {}\n", model));
+        }
+        out.push('\n');
+        
+        if let Some(system_prompt) = conversation.system_prompt.as_deref().filter(|s| !s.is_empty()) {
+            out.push_str("## Sistema\n\n");
+            out.push_str(system_prompt);
+            out.push_str("\n\n");
+        }
+        
+        for message in &exported.messages {
+            out.push_str(&format!("## {} — {}\n\n", role_label(&message.role), message.created_at));
+            out.push_str(&message.content);
+            if message.stopped {
+                out.push_str("\n\n_(interrompida)_");
+            }
+            out.push_str("\n\n");
+        }
+    }
+    
+    out
+}
+
+pub fn to_html(conversations: &[ExportedConversation]) -> String {
+    let title = match conversations {
+        [single] => single.conversation.title.clone(),
+        _ => "LLMpad".to_string(),
+    };
+    
+    let mut body = String::new();
+    
+    for exported in conversations {
+        let conversation = &exported.conversation;
+        body.push_str("<article class=\"conversation\">\n");
+        body.push_str(&format!("<h1>{}</h1>\n", escape_html(&conversation.title)));
+        body.push_str(&format!(
+            "<p class=\"meta\">{} · {}</p>\n",
+            escape_html(&conversation.created_at),
+            escape_html(conversation.model.as_deref().unwrap_or_default())
+        ));
+        
+        if let Some(system_prompt) = conversation.system_prompt.as_deref().filter(|s| !s.is_empty()) {
+            body.push_str(&format!(
+                "<section class=\"message system\"><h2>Sistema</h2><div class=\"content\">{}</div></section>\n",
+                escape_html(system_prompt)
+            ));
+        }
+        
+        for message in &exported.messages {
+            body.push_str(&format!(
+                "<section class=\"message {}\"><h2>{} <time>{}</time></h2><div class=\"content\">{}</div>{}</section>\n",
+                escape_html(&message.role),
+                escape_html(role_label(&message.role)),
+                escape_html(&message.created_at),
+                escape_html(&message.content),
+                if message.stopped { "<p class=\"stopped\">(interrompida)</p>" } else { "" }
+            ));
+        }
+        
+        body.push_str("</article>\n");
+    }
+    
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="pt-BR">
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; color: #1f2937; }}
+h1 {{ font-size: 1.5rem; }}
+h2 {{ font-size: 0.9rem; color: #6b7280; margin: 0 0 0.5rem; }}
+time {{ font-weight: normal; margin-left: 0.5rem; }}
+.meta {{ color: #6b7280; font-size: 0.85rem; }}
+.message {{ border-radius: 0.75rem; padding: 0.75rem 1rem; margin: 0.75rem 0; }}
+.message.user {{ background: #dbeafe; }}
+.message.assistant {{ background: #f3f4f6; }}
+.message.system {{ background: #fef3c7; }}
+.content {{ white-space: pre-wrap; word-wrap: break-word; }}
+.stopped {{ color: #b91c1c; font-size: 0.8rem; margin: 0.5rem 0 0; }}
+article + article {{ border-top: 1px solid #e5e7eb; margin-top: 2rem; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        escape_html(&title),
+        body
+    )
+}
+
+fn role_label(role: &str) -> &str {
+    match role {
+        "user" => "Você",
+        "assistant" => "Assistente",
+        "system" => "Sistema",
+        other => other,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Conversation, Message};
+    
+    const HOSTILE: &str = "<script>alert('oi & \"tchau\"')</script>";
+    
+    fn conversation() -> ExportedConversation {
+        ExportedConversation {
+            conversation: Conversation {
+                id: 1,
+                title: HOSTILE.to_string(),
+                created_at: "2024-01-01 00:00:00".to_string(),
+                updated_at: "2024-01-01 00:00:00".to_string(),
+                generation_params: None,
+                system_prompt: Some(HOSTILE.to_string()),
+                model: Some(HOSTILE.to_string()),
+                api_url: None,
+                provider_id: None,
+                current_message_id: Some(1),
+            },
+            messages: vec![Message {
+                id: 1,
+                conversation_id: 1,
+                role: "user\" onclick=\"x".to_string(),
+                content: HOSTILE.to_string(),
+                created_at: "2024-01-01 00:00:00".to_string(),
+                stopped: false,
+                model: None,
+                parent_message_id: None,
+            }],
+        }
+    }
+    
+    #[test]
+    fn html_special_characters_are_escaped() {
+        assert_eq!(
+            escape_html(HOSTILE),
+            "&lt;script&gt;alert(&#39;oi &amp; &quot;tchau&quot;&#39;)&lt;/script&gt;"
+        );
+        assert_eq!(escape_html("çà 🙂"), "çà 🙂");
+    }
+    
+    #[test]
+    fn html_export_never_contains_raw_markup_from_the_conversation() {
+        let html = to_html(&[conversation()]);
+        
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("alert('"));
+        assert!(!html.contains("\" onclick"));
+        assert!(html.contains("<title>&lt;script&gt;"));
+        assert!(html.contains("class=\"message user&quot; onclick=&quot;x\""));
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+/// What to do with the text received so far when a generation is cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelAction {
+    Discard,
+    KeepPartial,
+}
+
+/// Tracks in-flight generations so they can be cancelled by conversation id.
+#[derive(Default)]
+pub struct Generations {
+    active: Mutex<HashMap<i64, (u64, oneshot::Sender<CancelAction>)>>,
+    next_id: AtomicU64,
+}
+
+impl Generations {
+    pub fn register(&self, conversation_id: i64) -> Result<GenerationGuard<'_>, String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        
+        if active.contains_key(&conversation_id) {
+            return Err("Já existe uma geração em andamento nesta conversa".to_string());
+        }
+        
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        active.insert(conversation_id, (id, tx));
+        
+        Ok(GenerationGuard {
+            generations: self,
+            conversation_id,
+            id,
+            cancel: rx,
+        })
+    }
+    
+    pub fn cancel(&self, conversation_id: i64, action: CancelAction) -> Result<bool, String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        
+        match active.remove(&conversation_id) {
+            Some((_, tx)) => Ok(tx.send(action).is_ok()),
+            None => Ok(false),
+        }
+    }
+}
+
+pub struct GenerationGuard<'a> {
+    generations: &'a Generations,
+    conversation_id: i64,
+    id: u64,
+    pub cancel: oneshot::Receiver<CancelAction>,
+}
+
+impl Drop for GenerationGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.generations.active.lock() {
+            if active.get(&self.conversation_id).is_some_and(|(id, _)| *id == self.id) {
+                active.remove(&self.conversation_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,615 @@
+use serde_json::Value;
+use crate::export::EXPORT_FORMAT_NAME;
+use crate::models::{ExportDocument, ImportSource};
+
+pub struct ImportedConversation {
+    pub title: String,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub system_prompt: Option<String>,
+    pub model: Option<String>,
+    pub generation_params: Option<String>,
+    pub current_source_id: Option<i64>,
+    pub messages: Vec<ImportedMessage>,
+}
+
+pub struct ImportedMessage {
+    pub role: String,
+    pub content: String,
+    pub created_at: Option<String>,
+    pub model: Option<String>,
+    pub stopped: bool,
+    /// Ids from an LLMpad export, used to rebuild the message tree. Messages
+    /// without them are chained in the order they appear.
+    pub source_id: Option<i64>,
+    pub source_parent_id: Option<i64>,
+}
+
+#[derive(Default)]
+pub struct ParsedImport {
+    pub conversations: Vec<ImportedConversation>,
+    pub skipped_messages: usize,
+    pub warnings: Vec<String>,
+}
+
+pub fn detect_source(value: &Value) -> Option<ImportSource> {
+    if value.get("format").and_then(Value::as_str) == Some(EXPORT_FORMAT_NAME) {
+        return Some(ImportSource::Llmpad);
+    }
+    
+    let first = match value {
+        Value::Array(items) => items.first()?,
+        other => other,
+    };
+    
+    if first.get("mapping").is_some() {
+        Some(ImportSource::Chatgpt)
+    } else if first.get("chat").is_some() {
+        Some(ImportSource::OpenWebui)
+    } else {
+        None
+    }
+}
+
+pub fn parse(value: Value, source: ImportSource) -> Result<ParsedImport, String> {
+    match source {
+        ImportSource::Chatgpt => Ok(parse_chatgpt(value)),
+        ImportSource::OpenWebui => Ok(parse_open_webui(value)),
+        ImportSource::Llmpad => parse_llmpad(value),
+    }
+}
+
+/// Converts a Unix timestamp (seconds or milliseconds) into the
+/// `YYYY-MM-DD HH:MM:SS` UTC format SQLite's `datetime('now')` produces.
+fn format_timestamp(value: Option<&Value>) -> Option<String> {
+    let mut seconds = value?.as_f64()?;
+    if seconds > 1e12 {
+        seconds /= 1000.0;
+    }
+    
+    chrono::DateTime::from_timestamp(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn as_items(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        Value::Null => vec![],
+        other => vec![other],
+    }
+}
+
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .filter(|s| !s.is_empty())
+}
+
+/// Keeps user and assistant turns, turns a leading system message into the
+/// conversation's system prompt and counts everything else as skipped.
+fn push_message(
+    conversation: &mut ImportedConversation,
+    parsed: &mut ParsedImport,
+    message: ImportedMessage,
+) {
+    match message.role.as_str() {
+        "user" | "assistant" if !message.content.trim().is_empty() => {
+            conversation.messages.push(message)
+        }
+        "system"
+            if conversation.messages.is_empty()
+                && conversation.system_prompt.is_none()
+                && !message.content.trim().is_empty() =>
+        {
+            conversation.system_prompt = Some(message.content)
+        }
+        _ => parsed.skipped_messages += 1,
+    }
+}
+
+fn parse_chatgpt(value: Value) -> ParsedImport {
+    let mut parsed = ParsedImport::default();
+    
+    for (index, item) in as_items(value).into_iter().enumerate() {
+        let Some(mapping) = item.get("mapping").and_then(Value::as_object) else {
+            parsed.warnings.push(format!("Conversa {} sem campo 'mapping' ignorada", index + 1));
+            continue;
+        };
+        
+        let mut conversation = ImportedConversation {
+            title: string_field(&item, "title").unwrap_or_else(|| "Conversa importada".to_string()),
+            created_at: format_timestamp(item.get("create_time")),
+            updated_at: format_timestamp(item.get("update_time")),
+            system_prompt: None,
+            model: string_field(&item, "default_model_slug"),
+            generation_params: None,
+            current_source_id: None,
+            messages: vec![],
+        };
+        
+        let mut path = Vec::new();
+        let mut node_id = item
+            .get("current_node")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        
+        while let Some(id) = node_id {
+            let Some(node) = mapping.get(&id) else {
+                break;
+            };
+            if path.len() > mapping.len() {
+                parsed.warnings.push(format!("Ciclo detectado em '{}'", conversation.title));
+                break;
+            }
+            path.push(node);
+            node_id = node.get("parent").and_then(Value::as_str).map(str::to_string);
+        }
+        
+        for node in path.into_iter().rev() {
+            let Some(message) = node.get("message").filter(|m| !m.is_null()) else {
+                continue;
+            };
+            
+            let role = message
+                .pointer("/author/role")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            
+            let content = message.get("content");
+            let text = match content.and_then(|c| c.get("content_type")).and_then(Value::as_str) {
+                Some("text") | Some("multimodal_text") => content
+                    .and_then(|c| c.get("parts"))
+                    .and_then(Value::as_array)
+                    .map(|parts| {
+                        parts
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    })
+                    .unwrap_or_default(),
+                Some("code") => content
+                    .and_then(|c| c.get("text"))
+                    .and_then(Value::as_str)
+                    .map(|code| format!("```\n{}\n```", code))
+                    .unwrap_or_default(),
+                _ => String::new(),
+            };
+            
+            let is_hidden = message
+                .pointer("/metadata/is_visually_hidden_from_conversation")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            
+            if is_hidden {
+                parsed.skipped_messages += 1;
+                continue;
+            }
+            
+            let imported = ImportedMessage {
+                role,
+                content: text,
+                created_at: format_timestamp(message.get("create_time")),
+                model: message
+                    .pointer("/metadata/model_slug")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                stopped: false,
+                source_id: None,
+                source_parent_id: None,
+            };
+            
+            push_message(&mut conversation, &mut parsed, imported);
+        }
+        
+        parsed.conversations.push(conversation);
+    }
+    
+    parsed
+}
+
+fn parse_open_webui(value: Value) -> ParsedImport {
+    let mut parsed = ParsedImport::default();
+    
+    for (index, item) in as_items(value).into_iter().enumerate() {
+        let Some(chat) = item.get("chat") else {
+            parsed.warnings.push(format!("Conversa {} sem campo 'chat' ignorada", index + 1));
+            continue;
+        };
+        
+        let mut conversation = ImportedConversation {
+            title: string_field(&item, "title")
+                .or_else(|| string_field(chat, "title"))
+                .unwrap_or_else(|| "Conversa importada".to_string()),
+            created_at: format_timestamp(item.get("created_at").or_else(|| chat.get("timestamp"))),
+            updated_at: format_timestamp(item.get("updated_at")),
+            system_prompt: chat
+                .pointer("/params/system")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .filter(|s| !s.is_empty()),
+            model: chat
+                .get("models")
+                .and_then(Value::as_array)
+                .and_then(|models| models.first())
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            generation_params: None,
+            current_source_id: None,
+            messages: vec![],
+        };
+        
+        let history = chat.pointer("/history/messages").and_then(Value::as_object);
+        let current_id = chat.pointer("/history/currentId").and_then(Value::as_str);
+        
+        let messages: Vec<&Value> = match (history, current_id) {
+            (Some(history), Some(current_id)) => {
+                let mut path = Vec::new();
+                let mut node_id = Some(current_id.to_string());
+                while let Some(id) = node_id {
+                    let Some(node) = history.get(&id) else {
+                        break;
+                    };
+                    if path.len() > history.len() {
+                        parsed.warnings.push(format!("Ciclo detectado em '{}'", conversation.title));
+                        break;
+                    }
+                    path.push(node);
+                    node_id = node.get("parentId").and_then(Value::as_str).map(str::to_string);
+                }
+                path.reverse();
+                path
+            }
+            _ => chat
+                .get("messages")
+                .and_then(Value::as_array)
+                .map(|messages| messages.iter().collect())
+                .unwrap_or_default(),
+        };
+        
+        for message in messages {
+            let imported = ImportedMessage {
+                role: string_field(message, "role").unwrap_or_default(),
+                content: message
+                    .get("content")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                created_at: format_timestamp(message.get("timestamp")),
+                model: string_field(message, "model"),
+                stopped: false,
+                source_id: None,
+                source_parent_id: None,
+            };
+            
+            push_message(&mut conversation, &mut parsed, imported);
+        }
+        
+        parsed.conversations.push(conversation);
+    }
+    
+    parsed
+}
+
+fn parse_llmpad(value: Value) -> Result<ParsedImport, String> {
+    let document: ExportDocument = serde_json::from_value(value)
+        .map_err(|e| format!("Exportação do LLMpad inválida: {}", e))?;
+    
+    let mut parsed = ParsedImport::default();
+    
+    if document.version > crate::export::EXPORT_FORMAT_VERSION {
+        parsed.warnings.push(format!(
+            "Exportação na versão {} é mais nova que a suportada ({})",
+            document.version,
+            crate::export::EXPORT_FORMAT_VERSION
+        ));
+    }
+    
+    let has_tree = document.version >= 2;
+    
+    for exported in document.conversations {
+        let conversation = exported.conversation;
+        let mut imported = ImportedConversation {
+            title: conversation.title,
+            created_at: Some(conversation.created_at),
+            updated_at: Some(conversation.updated_at),
+            system_prompt: conversation.system_prompt,
+            model: conversation.model,
+            generation_params: conversation
+                .generation_params
+                .and_then(|p| serde_json::to_string(&p).ok()),
+            current_source_id: conversation.current_message_id.filter(|_| has_tree),
+            messages: vec![],
+        };
+        
+        for message in exported.messages {
+            let message = ImportedMessage {
+                role: message.role,
+                content: message.content,
+                created_at: Some(message.created_at),
+                model: message.model,
+                stopped: message.stopped,
+                source_id: Some(message.id).filter(|_| has_tree),
+                source_parent_id: message.parent_message_id.filter(|_| has_tree),
+            };
+            
+            match message.role.as_str() {
+                "user" | "assistant" => imported.messages.push(message),
+                _ => parsed.skipped_messages += 1,
+            }
+        }
+        
+        parsed.conversations.push(imported);
+    }
+    
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn chatgpt_node(id: &str, parent: Option<&str>, role: &str, text: &str) -> (String, Value) {
+        let message = json!({
+            "author": { "role": role },
+            "content": { "content_type": "text", "parts": [text] },
+            "create_time": 1700000000.5
+        });
+        (id.to_string(), json!({ "id": id, "parent": parent, "message": message }))
+    }
+    
+    fn chatgpt_export(nodes: Vec<(String, Value)>, current_node: &str) -> Value {
+        let mapping: serde_json::Map<String, Value> = nodes.into_iter().collect();
+        json!([{
+            "title": "Receitas",
+            "create_time": 1700000000,
+            "update_time": 1700000100,
+            "default_model_slug": "gpt-4o",
+            "current_node": current_node,
+            "mapping": mapping
+        }])
+    }
+    
+    fn contents(conversation: &ImportedConversation) -> Vec<(&str, &str)> {
+        conversation
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect()
+    }
+    
+    #[test]
+    fn detects_each_source() {
+        assert_eq!(detect_source(&json!({ "format": "llmpad", "version": 2 })), Some(ImportSource::Llmpad));
+        assert_eq!(detect_source(&json!([{ "mapping": {} }])), Some(ImportSource::Chatgpt));
+        assert_eq!(detect_source(&json!({ "chat": {} })), Some(ImportSource::OpenWebui));
+        assert_eq!(detect_source(&json!([])), None);
+        assert_eq!(detect_source(&json!({ "messages": [] })), None);
+    }
+    
+    #[test]
+    fn formats_seconds_and_milliseconds_alike() {
+        assert_eq!(format_timestamp(Some(&json!(1700000000))).as_deref(), Some("2023-11-14 22:13:20"));
+        assert_eq!(format_timestamp(Some(&json!(1700000000000u64))).as_deref(), Some("2023-11-14 22:13:20"));
+        assert_eq!(format_timestamp(Some(&json!("ontem"))), None);
+        assert_eq!(format_timestamp(None), None);
+    }
+    
+    #[test]
+    fn chatgpt_follows_the_current_branch_from_the_root() {
+        let export = chatgpt_export(
+            vec![
+                ("root".to_string(), json!({ "id": "root", "parent": null, "message": null })),
+                chatgpt_node("system", Some("root"), "system", "Seja breve."),
+                chatgpt_node("question", Some("system"), "user", "Como fazer pão?"),
+                chatgpt_node("old", Some("question"), "assistant", "Resposta antiga"),
+                chatgpt_node("new", Some("question"), "assistant", "Farinha, água e sal."),
+            ],
+            "new",
+        );
+        
+        let parsed = parse(export, ImportSource::Chatgpt).unwrap();
+        let conversation = &parsed.conversations[0];
+        
+        assert_eq!(conversation.title, "Receitas");
+        assert_eq!(conversation.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(conversation.created_at.as_deref(), Some("2023-11-14 22:13:20"));
+        assert_eq!(conversation.system_prompt.as_deref(), Some("Seja breve."));
+        assert_eq!(
+            contents(conversation),
+            vec![("user", "Como fazer pão?"), ("assistant", "Farinha, água e sal.")]
+        );
+        assert_eq!(parsed.skipped_messages, 0);
+    }
+    
+    #[test]
+    fn chatgpt_skips_hidden_tool_and_empty_messages() {
+        let (hidden_id, mut hidden) = chatgpt_node("hidden", None, "system", "contexto interno");
+        hidden["message"]["metadata"] = json!({ "is_visually_hidden_from_conversation": true });
+        let (code_id, mut code) = chatgpt_node("code", Some("question"), "assistant", "");
+        code["message"]["content"] = json!({ "content_type": "code", "text": "print(1)" });
+        
+        let export = chatgpt_export(
+            vec![
+                (hidden_id, hidden),
+                chatgpt_node("question", Some("hidden"), "user", "Rode isto"),
+                (code_id, code),
+                chatgpt_node("tool", Some("code"), "tool", "1"),
+                chatgpt_node("empty", Some("tool"), "assistant", "  "),
+            ],
+            "empty",
+        );
+        
+        let parsed = parse(export, ImportSource::Chatgpt).unwrap();
+        let conversation = &parsed.conversations[0];
+        
+        assert_eq!(conversation.system_prompt, None);
+        assert_eq!(
+            contents(conversation),
+            vec![("user", "Rode isto"), ("assistant", "```\nprint(1)\n```")]
+        );
+        assert_eq!(parsed.skipped_messages, 3);
+    }
+    
+    #[test]
+    fn chatgpt_stops_at_a_parent_cycle() {
+        let export = chatgpt_export(
+            vec![
+                chatgpt_node("a", Some("b"), "user", "Oi"),
+                chatgpt_node("b", Some("a"), "assistant", "Olá"),
+            ],
+            "b",
+        );
+        
+        let parsed = parse(export, ImportSource::Chatgpt).unwrap();
+        
+        assert_eq!(parsed.conversations.len(), 1);
+        assert_eq!(parsed.warnings, vec!["Ciclo detectado em 'Receitas'".to_string()]);
+    }
+    
+    #[test]
+    fn chatgpt_warns_about_items_without_mapping() {
+        let parsed = parse(json!([{ "title": "Sem mapping" }]), ImportSource::Chatgpt).unwrap();
+        
+        assert!(parsed.conversations.is_empty());
+        assert_eq!(parsed.warnings, vec!["Conversa 1 sem campo 'mapping' ignorada".to_string()]);
+    }
+    
+    #[test]
+    fn open_webui_follows_history_to_the_current_message() {
+        let export = json!([{
+            "title": "Viagem",
+            "created_at": 1700000000,
+            "chat": {
+                "models": ["llama3"],
+                "params": { "system": "Responda em português." },
+                "history": {
+                    "currentId": "b2",
+                    "messages": {
+                        "u1": { "id": "u1", "parentId": null, "role": "user", "content": "Para onde ir?" },
+                        "b1": { "id": "b1", "parentId": "u1", "role": "assistant", "content": "Lisboa." },
+                        "b2": { "id": "b2", "parentId": "u1", "role": "assistant", "content": "Porto.", "model": "llama3" }
+                    }
+                },
+                "messages": []
+            }
+        }]);
+        
+        let parsed = parse(export, ImportSource::OpenWebui).unwrap();
+        let conversation = &parsed.conversations[0];
+        
+        assert_eq!(conversation.title, "Viagem");
+        assert_eq!(conversation.model.as_deref(), Some("llama3"));
+        assert_eq!(conversation.system_prompt.as_deref(), Some("Responda em português."));
+        assert_eq!(contents(conversation), vec![("user", "Para onde ir?"), ("assistant", "Porto.")]);
+        assert_eq!(conversation.messages[1].model.as_deref(), Some("llama3"));
+    }
+    
+    #[test]
+    fn open_webui_falls_back_to_the_flat_message_list() {
+        let export = json!({
+            "chat": {
+                "title": "Sem histórico",
+                "messages": [
+                    { "role": "user", "content": "Oi" },
+                    { "role": "assistant", "content": "Olá!" },
+                    { "role": "system", "content": "Tarde demais para ser prompt" }
+                ]
+            }
+        });
+        
+        let parsed = parse(export, ImportSource::OpenWebui).unwrap();
+        let conversation = &parsed.conversations[0];
+        
+        assert_eq!(conversation.title, "Sem histórico");
+        assert_eq!(conversation.system_prompt, None);
+        assert_eq!(contents(conversation), vec![("user", "Oi"), ("assistant", "Olá!")]);
+        assert_eq!(parsed.skipped_messages, 1);
+    }
+    
+    fn llmpad_export(version: u32) -> Value {
+        let message = |id: i64, parent: Option<i64>, role: &str, content: &str| {
+            json!({
+                "id": id,
+                "conversation_id": 7,
+                "role": role,
+                "content": content,
+                "created_at": "2024-01-01 10:00:00",
+                "stopped": false,
+                "model": null,
+                "parent_message_id": parent
+            })
+        };
+        
+        json!({
+            "format": "llmpad",
+            "version": version,
+            "exported_at": "2024-01-02 10:00:00",
+            "conversations": [{
+                "id": 7,
+                "title": "Exportada",
+                "created_at": "2024-01-01 10:00:00",
+                "updated_at": "2024-01-01 10:05:00",
+                "generation_params": null,
+                "system_prompt": "Seja gentil.",
+                "model": "llama3",
+                "api_url": null,
+                "provider_id": null,
+                "current_message_id": 12,
+                "messages": [
+                    message(10, None, "user", "Oi"),
+                    message(11, Some(10), "assistant", "Olá"),
+                    message(12, Some(10), "assistant", "Olá de novo"),
+                    message(13, Some(12), "system", "ignorada")
+                ]
+            }]
+        })
+    }
+    
+    #[test]
+    fn llmpad_keeps_the_message_tree() {
+        let parsed = parse(llmpad_export(2), ImportSource::Llmpad).unwrap();
+        let conversation = &parsed.conversations[0];
+        
+        assert_eq!(conversation.title, "Exportada");
+        assert_eq!(conversation.system_prompt.as_deref(), Some("Seja gentil."));
+        assert_eq!(conversation.current_source_id, Some(12));
+        
+        let tree: Vec<(Option<i64>, Option<i64>)> = conversation
+            .messages
+            .iter()
+            .map(|m| (m.source_id, m.source_parent_id))
+            .collect();
+        assert_eq!(tree, vec![(Some(10), None), (Some(11), Some(10)), (Some(12), Some(10))]);
+        assert_eq!(parsed.skipped_messages, 1);
+        assert!(parsed.warnings.is_empty());
+    }
+    
+    #[test]
+    fn llmpad_version_one_is_chained_and_newer_versions_warn() {
+        let parsed = parse(llmpad_export(1), ImportSource::Llmpad).unwrap();
+        let conversation = &parsed.conversations[0];
+        
+        assert_eq!(conversation.current_source_id, None);
+        assert!(conversation.messages.iter().all(|m| m.source_id.is_none() && m.source_parent_id.is_none()));
+        
+        let parsed = parse(llmpad_export(99), ImportSource::Llmpad).unwrap();
+        assert_eq!(
+            parsed.warnings,
+            vec!["Exportação na versão 99 é mais nova que a suportada (2)".to_string()]
+        );
+    }
+    
+    #[test]
+    fn llmpad_rejects_a_malformed_export() {
+        let error = parse(json!({ "format": "llmpad", "version": 2 }), ImportSource::Llmpad)
+            .err()
+            .unwrap();
+        
+        assert!(error.starts_with("Exportação do LLMpad inválida:"));
+    }
+}
@@ -0,0 +1,252 @@
+use rusqlite::{Connection, Result as SqliteResult};
+use std::path::{Path, PathBuf};
+
+/// Target chunk size in characters, about 250 tokens.
+pub const CHUNK_SIZE: usize = 1000;
+
+/// Characters repeated between consecutive chunks so that a sentence cut at
+/// a boundary still appears whole in one of them.
+pub const CHUNK_OVERLAP: usize = 200;
+
+/// Chunks retrieved for each turn.
+pub const TOP_K: usize = 4;
+
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "dist", "build", "__pycache__"];
+
+/// Files under `root`, skipping hidden entries and common build folders.
+pub fn collect_files(root: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Erro ao ler pasta {}: {}", dir.display(), e))?;
+        
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            
+            let path = entry.path();
+            if path.is_dir() {
+                if !SKIPPED_DIRS.contains(&name.as_str()) {
+                    pending.push(path);
+                }
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    
+    files.sort();
+    Ok(files)
+}
+
+/// Splits text into chunks of about `CHUNK_SIZE` characters, preferring to
+/// cut at paragraph, line or sentence boundaries.
+pub fn chunk_text(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    
+    while start < chars.len() {
+        let mut end = (start + CHUNK_SIZE).min(chars.len());
+        
+        if end < chars.len() {
+            let window = &chars[start..end];
+            let min_cut = CHUNK_SIZE / 2;
+            let cut = find_break(window, min_cut, &['\n', '\n'])
+                .or_else(|| find_break(window, min_cut, &['\n']))
+                .or_else(|| find_break(window, min_cut, &['.', ' ']))
+                .or_else(|| find_break(window, min_cut, &[' ']));
+            if let Some(cut) = cut {
+                end = start + cut;
+            }
+        }
+        
+        let chunk: String = chars[start..end].iter().collect();
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        
+        if end == chars.len() {
+            break;
+        }
+        let mut next = end.saturating_sub(CHUNK_OVERLAP).max(start + 1);
+        while next < end && !chars[next - 1].is_whitespace() {
+            next += 1;
+        }
+        start = next;
+    }
+    
+    chunks
+}
+
+/// Position right after the last occurrence of `pattern` in `window`, if it
+/// is past `min`.
+fn find_break(window: &[char], min: usize, pattern: &[char]) -> Option<usize> {
+    window
+        .windows(pattern.len())
+        .rposition(|candidate| candidate == pattern)
+        .map(|index| index + pattern.len())
+        .filter(|cut| *cut >= min)
+}
+
+pub fn encode_embedding(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+pub fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+pub struct StoredChunk {
+    pub id: i64,
+    pub source: String,
+    pub content: String,
+    pub embedding: Vec<f32>,
+}
+
+pub fn chunks(conn: &Connection, knowledge_base_id: i64) -> SqliteResult<Vec<StoredChunk>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source, content, embedding FROM knowledge_chunks
+         WHERE knowledge_base_id = ?1 ORDER BY id ASC",
+    )?;
+    
+    let chunks = stmt
+        .query_map([knowledge_base_id], |row| {
+            Ok(StoredChunk {
+                id: row.get(0)?,
+                source: row.get(1)?,
+                content: row.get(2)?,
+                embedding: decode_embedding(&row.get::<_, Vec<u8>>(3)?),
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(chunks)
+}
+
+pub fn attached_to(conn: &Connection, conversation_id: i64) -> SqliteResult<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT knowledge_base_id FROM conversation_knowledge_bases
+         WHERE conversation_id = ?1 ORDER BY knowledge_base_id",
+    )?;
+    
+    let ids = stmt
+        .query_map([conversation_id], |row| row.get(0))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn char_len(text: &str) -> usize {
+        text.chars().count()
+    }
+    
+    /// Characters at the end of `a` that `b` starts with.
+    fn overlap(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        (0..=a.len().min(b.len()))
+            .rev()
+            .find(|k| a[a.len() - k..] == b[..*k])
+            .unwrap_or(0)
+    }
+    
+    #[test]
+    fn short_or_blank_text_is_one_chunk_or_none() {
+        assert_eq!(chunk_text("  Olá, mundo!  "), vec!["Olá, mundo!"]);
+        assert_eq!(chunk_text(&"é".repeat(CHUNK_SIZE)), vec!["é".repeat(CHUNK_SIZE)]);
+        assert!(chunk_text("").is_empty());
+        assert!(chunk_text(" \n\n\t ").is_empty());
+    }
+    
+    #[test]
+    fn text_without_breaks_is_cut_at_exactly_chunk_size_characters() {
+        let text = "çã🙂".repeat(850);
+        let chunks = chunk_text(&text);
+        
+        let lengths: Vec<usize> = chunks.iter().map(|c| char_len(c)).collect();
+        assert_eq!(lengths, vec![1000, 1000, 550]);
+        assert_eq!(chunks.concat(), text);
+    }
+    
+    #[test]
+    fn paragraph_breaks_are_preferred_and_overlap_starts_on_a_word() {
+        let first = "ç ".repeat(350);
+        let second = "ã ".repeat(400);
+        let text = format!("{}\n\n{}", first, second);
+        let chars: Vec<char> = text.chars().collect();
+        
+        let chunks = chunk_text(&text);
+        
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], first.trim());
+        assert_eq!(chunks[1], chars[502..].iter().collect::<String>().trim());
+        // The shared 200 characters end in " \n\n", which trimming drops.
+        assert_eq!(overlap(&chunks[0], &chunks[1]), CHUNK_OVERLAP - 3);
+    }
+    
+    #[test]
+    fn consecutive_chunks_overlap_by_up_to_chunk_overlap_characters() {
+        let text = (0..1000).map(|i| format!("ação{:04}", i)).collect::<Vec<_>>().join(" ");
+        let chunks = chunk_text(&text);
+        
+        assert!(chunks.len() > 5);
+        for pair in chunks.windows(2) {
+            assert!(char_len(&pair[0]) <= CHUNK_SIZE);
+            let shared = overlap(&pair[0], &pair[1]);
+            assert!((CHUNK_OVERLAP - 9..=CHUNK_OVERLAP).contains(&shared), "{}", shared);
+            assert!(pair[1].starts_with("ação"));
+        }
+        assert!(chunks.last().unwrap().ends_with("ação0999"));
+    }
+    
+    #[test]
+    fn cosine_similarity_never_returns_nan() {
+        assert_eq!(cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]), -1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[0.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[], &[]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 2.0, 3.0], &[1.0, 2.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[]), 0.0);
+    }
+    
+    #[test]
+    fn embeddings_round_trip_through_bytes() {
+        let vector = vec![0.0, -1.5, 0.123_456_79, f32::MAX, f32::MIN_POSITIVE, -0.0];
+        let bytes = encode_embedding(&vector);
+        
+        assert_eq!(bytes.len(), vector.len() * 4);
+        assert_eq!(decode_embedding(&bytes), vector);
+        assert_eq!(decode_embedding(&bytes[..9]), vector[..2]);
+        assert!(decode_embedding(&[]).is_empty());
+    }
+}
@@ -1,9 +1,27 @@
 mod models;
+mod attachments;
+mod branches;
+mod context;
+mod documents;
+mod modelfile;
 mod database;
+mod export;
+mod import;
+mod migrations;
+mod secrets;
+mod generations;
+mod tools;
+mod knowledge;
+mod mcp;
+mod downloads;
 mod commands;
 
 use tauri::Manager;
 use database::Database;
+use downloads::Downloads;
+use generations::Generations;
+use mcp::McpServers;
+use tools::{ToolApprovals, ToolRegistry};
 use commands::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -11,27 +29,81 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .setup(|app| {
-            let db = Database::new(&app.handle()).expect("Failed to initialize database");
+            let db = Database::new(app.handle()).expect("Failed to initialize database");
             app.manage(db);
+            app.manage(Generations::default());
+            app.manage(ToolRegistry::default());
+            app.manage(ToolApprovals::default());
+            app.manage(McpServers::default());
+            app.manage(Downloads::default());
+            mcp::start_enabled(app.handle());
+            downloads::resume_interrupted(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_conversations,
             create_conversation,
             update_conversation_title,
+            update_conversation_params,
+            update_conversation_system_prompt,
+            set_conversation_model,
             delete_conversation,
             get_messages,
             save_message,
+            list_message_alternatives,
+            select_message_alternative,
+            search_messages,
+            export_conversation,
+            export_all_conversations,
+            import_conversations,
+            add_attachment,
+            delete_attachment,
+            list_attachments,
+            get_attachment_data,
+            get_knowledge_bases,
+            create_knowledge_base,
+            delete_knowledge_base,
+            ingest_knowledge_base,
+            get_conversation_knowledge_bases,
+            set_conversation_knowledge_bases,
+            get_mcp_servers,
+            get_mcp_server_status,
+            create_mcp_server,
+            update_mcp_server,
+            delete_mcp_server,
+            set_mcp_server_enabled,
+            restart_mcp_server,
+            respond_tool_approval,
             get_settings,
             save_settings,
+            get_providers,
+            create_provider,
+            update_provider,
+            delete_provider,
+            set_default_provider,
             chat_completion,
             send_message_complete,
+            send_message_stream,
+            regenerate_message,
+            edit_message,
+            cancel_generation,
             get_modelfiles,
             get_modelfiles_with_status,
+            create_modelfile,
+            create_modelfile_from_fields,
+            save_modelfile,
+            rename_modelfile,
+            delete_modelfile,
             list_ollama_models,
             check_base_model,
             create_ollama_model,
             pull_ollama_model,
+            queue_model_download,
+            list_downloads,
+            pause_download,
+            cancel_download,
+            resume_download,
+            clear_finished_downloads,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
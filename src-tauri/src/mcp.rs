@@ -0,0 +1,850 @@
+use rusqlite::{Connection, Result as SqliteResult};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::oneshot;
+use crate::database::{mcp_server_from_row, Database, MCP_SERVER_COLUMNS};
+use crate::models::{McpServer, McpServerState, McpServerStatus, McpTransport};
+use crate::tools::{Tool, ToolFuture, ToolRegistry};
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Consecutive restarts of a crashing server before it is left failed.
+const MAX_RESTARTS: u32 = 5;
+
+/// A session that lasted this long resets the restart count.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Interval between liveness pings of HTTP servers.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest tool name accepted by OpenAI-compatible APIs.
+const MAX_TOOL_NAME: usize = 64;
+
+pub fn enabled_servers(conn: &Connection) -> SqliteResult<Vec<McpServer>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM mcp_servers WHERE enabled = 1 ORDER BY id",
+        MCP_SERVER_COLUMNS
+    ))?;
+    
+    let servers = stmt
+        .query_map([], mcp_server_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    
+    Ok(servers)
+}
+
+/// Prefix of the registry names of a server's tools.
+fn tool_prefix(server_id: i64) -> String {
+    format!("mcp_{}_", server_id)
+}
+
+/// Registry name of an MCP tool, restricted to the characters and length
+/// chat APIs accept for function names. A name that had to be changed gets a
+/// hash of the original, so `a.b` and `a_b`, or two long names sharing their
+/// first 64 characters, do not end up as the same tool.
+fn tool_name(server_id: i64, remote_name: &str) -> String {
+    let name: String = remote_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let full = format!("{}{}", tool_prefix(server_id), name);
+    
+    if name == remote_name && full.len() <= MAX_TOOL_NAME {
+        return full;
+    }
+    
+    let hash = format!("{:x}", Sha256::digest(remote_name.as_bytes()));
+    let suffix = &hash[..8];
+    format!("{}_{}", &full[..full.len().min(MAX_TOOL_NAME - suffix.len() - 1)], suffix)
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// JSON-RPC connection to an MCP server.
+pub struct McpClient {
+    transport: Transport,
+    next_id: AtomicU64,
+}
+
+enum Transport {
+    Stdio(StdioTransport),
+    Http(HttpTransport),
+}
+
+impl McpClient {
+    fn new(transport: Transport) -> Self {
+        McpClient {
+            transport,
+            next_id: AtomicU64::new(1),
+        }
+    }
+    
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        
+        let exchange = async {
+            match &self.transport {
+                Transport::Stdio(stdio) => stdio.exchange(id, &message).await,
+                Transport::Http(http) => http
+                    .send(&message, Some(id))
+                    .await?
+                    .ok_or_else(|| "Resposta vazia do servidor MCP".to_string()),
+            }
+        };
+        
+        let response = match tokio::time::timeout(REQUEST_TIMEOUT, exchange).await {
+            Ok(response) => response?,
+            Err(_) => {
+                if let Transport::Stdio(stdio) = &self.transport {
+                    stdio.forget(id);
+                }
+                return Err(format!("Tempo esgotado aguardando resposta do servidor MCP ({})", method));
+            }
+        };
+        
+        if let Some(error) = response.get("error") {
+            return Err(format!(
+                "Erro do servidor MCP: {} ({})",
+                error.get("message").and_then(Value::as_str).unwrap_or("erro desconhecido"),
+                error.get("code").and_then(Value::as_i64).unwrap_or_default()
+            ));
+        }
+        
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+    
+    pub async fn notify(&self, method: &str) -> Result<(), String> {
+        let message = json!({ "jsonrpc": "2.0", "method": method });
+        
+        match &self.transport {
+            Transport::Stdio(stdio) => write_message(&stdio.stdin, &message).await,
+            Transport::Http(http) => http.send(&message, None).await.map(|_| ()),
+        }
+    }
+    
+    /// Runs the `initialize` handshake.
+    async fn initialize(&self) -> Result<(), String> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "LLMpad", "version": env!("CARGO_PKG_VERSION") },
+                }),
+            )
+            .await?;
+        
+        if let Transport::Http(http) = &self.transport {
+            let version = result
+                .get("protocolVersion")
+                .and_then(Value::as_str)
+                .unwrap_or(PROTOCOL_VERSION)
+                .to_string();
+            if let Ok(mut protocol_version) = http.protocol_version.lock() {
+                *protocol_version = Some(version);
+            }
+        }
+        
+        self.notify("notifications/initialized").await
+    }
+    
+    async fn list_tools(&self) -> Result<Vec<RemoteTool>, String> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            
+            let page: ToolsPage = serde_json::from_value(result)
+                .map_err(|e| format!("Resposta inválida de tools/list: {}", e))?;
+            tools.extend(page.tools);
+            
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(tools),
+            }
+        }
+    }
+    
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self
+            .request("tools/call", json!({ "name": name, "arguments": arguments }))
+            .await?;
+        
+        let text = result_text(&result);
+        if result.get("isError").and_then(Value::as_bool).unwrap_or(false) {
+            Err(text)
+        } else {
+            Ok(text)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ToolsPage {
+    #[serde(default)]
+    tools: Vec<RemoteTool>,
+    #[serde(rename = "nextCursor", default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoteTool {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(rename = "inputSchema", default)]
+    input_schema: Option<Value>,
+}
+
+/// Flattens the content blocks of a `tools/call` result into the text sent
+/// back to the model.
+fn result_text(result: &Value) -> String {
+    let parts: Vec<String> = result
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|content| {
+            content
+                .iter()
+                .map(|block| {
+                    let field = |name: &str| block.get(name).and_then(Value::as_str).unwrap_or_default();
+                    match field("type") {
+                        "text" => field("text").to_string(),
+                        kind @ ("image" | "audio") => format!("[{} {}]", kind, field("mimeType")),
+                        "resource" => {
+                            let resource = &block["resource"];
+                            resource
+                                .get("text")
+                                .or_else(|| resource.get("uri"))
+                                .and_then(Value::as_str)
+                                .unwrap_or_default()
+                                .to_string()
+                        }
+                        "resource_link" => field("uri").to_string(),
+                        _ => block.to_string(),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    
+    if parts.is_empty() {
+        return result
+            .get("structuredContent")
+            .map(Value::to_string)
+            .unwrap_or_default();
+    }
+    
+    parts.join("\n")
+}
+
+/// Newline-delimited JSON-RPC over the stdin and stdout of a child process.
+struct StdioTransport {
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+}
+
+impl StdioTransport {
+    async fn exchange(&self, id: u64, message: &Value) -> Result<Value, String> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().map_err(|e| e.to_string())?.insert(id, tx);
+        
+        if let Err(e) = write_message(&self.stdin, message).await {
+            self.forget(id);
+            return Err(e);
+        }
+        
+        rx.await.map_err(|_| "O servidor MCP encerrou a conexão".to_string())
+    }
+    
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+}
+
+async fn write_message(stdin: &tokio::sync::Mutex<ChildStdin>, message: &Value) -> Result<(), String> {
+    let mut line = message.to_string();
+    line.push('\n');
+    
+    let mut stdin = stdin.lock().await;
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("Erro ao enviar mensagem ao servidor MCP: {}", e))?;
+    stdin
+        .flush()
+        .await
+        .map_err(|e| format!("Erro ao enviar mensagem ao servidor MCP: {}", e))
+}
+
+/// Routes responses read from the server's stdout to the pending requests and
+/// answers the server's own requests (only `ping` is supported). Non-JSON lines
+/// are ignored since some servers log to stdout.
+async fn read_messages(stdout: ChildStdout, stdin: Arc<tokio::sync::Mutex<ChildStdin>>, pending: Pending) {
+    let mut lines = BufReader::new(stdout).lines();
+    
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        
+        if message.get("method").is_some() {
+            let Some(id) = message.get("id") else {
+                continue;
+            };
+            let reply = if message["method"] == "ping" {
+                json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+            } else {
+                json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": "Method not found" } })
+            };
+            let _ = write_message(&stdin, &reply).await;
+            continue;
+        }
+        
+        let Some(id) = message.get("id").and_then(Value::as_u64) else {
+            continue;
+        };
+        let sender = pending.lock().ok().and_then(|mut pending| pending.remove(&id));
+        if let Some(sender) = sender {
+            let _ = sender.send(message);
+        }
+    }
+    
+    if let Ok(mut pending) = pending.lock() {
+        pending.clear();
+    }
+}
+
+/// Streamable HTTP: every message is POSTed to the server URL, which answers
+/// with plain JSON or with an SSE stream carrying the response.
+struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    session_id: Mutex<Option<String>>,
+    protocol_version: Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    /// Sends a message and, for requests (`id` set), returns the response with
+    /// that id.
+    async fn send(&self, message: &Value, id: Option<u64>) -> Result<Option<Value>, String> {
+        let session_id = self.session_id.lock().map_err(|e| e.to_string())?.clone();
+        let protocol_version = self.protocol_version.lock().map_err(|e| e.to_string())?.clone();
+        
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        if let Some(session_id) = session_id {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+        if let Some(protocol_version) = protocol_version {
+            request = request.header("MCP-Protocol-Version", protocol_version);
+        }
+        
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| format!("Erro ao conectar ao servidor MCP: {}", e))?;
+        
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Servidor MCP retornou {}: {}", status, body));
+        }
+        
+        if let Some(session_id) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|value| value.to_str().ok())
+        {
+            *self.session_id.lock().map_err(|e| e.to_string())? = Some(session_id.to_string());
+        }
+        
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        
+        let is_stream = response
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        
+        if !is_stream {
+            let body: Value = response
+                .json()
+                .await
+                .map_err(|e| format!("Resposta inválida do servidor MCP: {}", e))?;
+            return Ok(find_response(body, id));
+        }
+        
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut data = String::new();
+        
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Erro ao ler stream do servidor MCP: {}", e))?
+        {
+            buffer.extend_from_slice(&chunk);
+            
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\r', '\n']);
+                
+                if let Some(value) = line.strip_prefix("data:") {
+                    if !data.is_empty() {
+                        data.push('\n');
+                    }
+                    data.push_str(value.strip_prefix(' ').unwrap_or(value));
+                } else if line.is_empty() && !data.is_empty() {
+                    let event = std::mem::take(&mut data);
+                    let response = serde_json::from_str(&event).ok().and_then(|body| find_response(body, id));
+                    if response.is_some() {
+                        return Ok(response);
+                    }
+                }
+            }
+        }
+        
+        Err("O servidor MCP encerrou o stream sem responder".to_string())
+    }
+}
+
+/// The message answering request `id` in a body holding a single message or
+/// a batch.
+fn find_response(body: Value, id: u64) -> Option<Value> {
+    let is_response = |message: &Value| message.get("id").and_then(Value::as_u64) == Some(id);
+    
+    match body {
+        Value::Array(messages) => messages.into_iter().find(is_response),
+        message if is_response(&message) => Some(message),
+        _ => None,
+    }
+}
+
+/// A connected and initialized server. Dropping it kills a stdio server.
+struct Session {
+    client: Arc<McpClient>,
+    process: Option<(Child, Arc<Mutex<String>>)>,
+}
+
+impl Session {
+    async fn connect(server: &McpServer) -> Result<Session, String> {
+        let session = match server.transport {
+            McpTransport::Stdio => Session::spawn(server)?,
+            McpTransport::Http => {
+                let url = server
+                    .url
+                    .clone()
+                    .ok_or_else(|| "Servidor MCP sem URL".to_string())?;
+                Session {
+                    client: Arc::new(McpClient::new(Transport::Http(HttpTransport {
+                        client: reqwest::Client::new(),
+                        url,
+                        session_id: Mutex::new(None),
+                        protocol_version: Mutex::new(None),
+                    }))),
+                    process: None,
+                }
+            }
+        };
+        
+        session.client.initialize().await?;
+        Ok(session)
+    }
+    
+    fn spawn(server: &McpServer) -> Result<Session, String> {
+        let command = server
+            .command
+            .as_deref()
+            .filter(|command| !command.trim().is_empty())
+            .ok_or_else(|| "Servidor MCP sem comando".to_string())?;
+        
+        let mut child = Command::new(command)
+            .args(&server.args)
+            .envs(&server.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Erro ao iniciar o servidor MCP '{}': {}", command, e))?;
+        
+        let (Some(stdin), Some(stdout), Some(stderr)) = (child.stdin.take(), child.stdout.take(), child.stderr.take())
+        else {
+            return Err("Não foi possível conectar à entrada e saída do servidor MCP".to_string());
+        };
+        
+        let stdin = Arc::new(tokio::sync::Mutex::new(stdin));
+        let pending = Pending::default();
+        tauri::async_runtime::spawn(read_messages(stdout, stdin.clone(), pending.clone()));
+        
+        let last_error = Arc::new(Mutex::new(String::new()));
+        let stderr_tail = last_error.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if !line.trim().is_empty() {
+                    if let Ok(mut tail) = stderr_tail.lock() {
+                        *tail = line;
+                    }
+                }
+            }
+        });
+        
+        Ok(Session {
+            client: Arc::new(McpClient::new(Transport::Stdio(StdioTransport { stdin, pending }))),
+            process: Some((child, last_error)),
+        })
+    }
+    
+    /// Resolves with the reason once the server is gone: the process exited
+    /// or, over HTTP, a ping failed.
+    async fn closed(&mut self) -> String {
+        match &mut self.process {
+            Some((child, last_error)) => {
+                let status = match child.wait().await {
+                    Ok(status) => format!("O servidor MCP encerrou ({})", status),
+                    Err(e) => format!("Erro ao aguardar o servidor MCP: {}", e),
+                };
+                let last_error = last_error.lock().map(|tail| tail.clone()).unwrap_or_default();
+                
+                if last_error.is_empty() {
+                    status
+                } else {
+                    format!("{}: {}", status, last_error)
+                }
+            }
+            None => loop {
+                tokio::time::sleep(PING_INTERVAL).await;
+                if let Err(e) = self.client.request("ping", json!({})).await {
+                    return e;
+                }
+            },
+        }
+    }
+}
+
+/// An MCP tool exposed to models through the `ToolRegistry`.
+struct McpTool {
+    name: String,
+    description: String,
+    parameters: Value,
+    remote_name: String,
+    require_approval: bool,
+    client: Arc<McpClient>,
+}
+
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn description(&self) -> &str {
+        &self.description
+    }
+    
+    fn parameters(&self) -> Value {
+        self.parameters.clone()
+    }
+    
+    fn call(&self, arguments: Value) -> ToolFuture<'_> {
+        Box::pin(self.client.call_tool(&self.remote_name, arguments))
+    }
+    
+    fn requires_approval(&self) -> bool {
+        self.require_approval
+    }
+}
+
+/// Supervisors of the running MCP servers and their last reported status.
+#[derive(Default)]
+pub struct McpServers {
+    running: tokio::sync::Mutex<HashMap<i64, Supervisor>>,
+    statuses: Mutex<HashMap<i64, McpServerStatus>>,
+}
+
+struct Supervisor {
+    stop: oneshot::Sender<()>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl McpServers {
+    /// Starts supervising `server`, replacing a supervisor already running
+    /// for it.
+    pub async fn start(&self, app: &AppHandle, server: McpServer) {
+        let mut running = self.running.lock().await;
+        
+        if let Some(supervisor) = running.remove(&server.id) {
+            supervisor.shutdown().await;
+        }
+        
+        let (stop, stop_rx) = oneshot::channel();
+        let server_id = server.id;
+        let task = tauri::async_runtime::spawn(supervise(app.clone(), server, stop_rx));
+        running.insert(server_id, Supervisor { stop, task });
+    }
+    
+    /// Stops the server and waits until its tools are unregistered.
+    pub async fn stop(&self, server_id: i64) {
+        let supervisor = self.running.lock().await.remove(&server_id);
+        
+        if let Some(supervisor) = supervisor {
+            supervisor.shutdown().await;
+        }
+    }
+    
+    pub fn statuses(&self) -> Vec<McpServerStatus> {
+        let Ok(statuses) = self.statuses.lock() else {
+            return vec![];
+        };
+        
+        let mut statuses: Vec<_> = statuses.values().cloned().collect();
+        statuses.sort_by_key(|status| status.server_id);
+        statuses
+    }
+    
+    pub fn forget(&self, server_id: i64) {
+        if let Ok(mut statuses) = self.statuses.lock() {
+            statuses.remove(&server_id);
+        }
+    }
+    
+    fn report(&self, app: &AppHandle, status: McpServerStatus) {
+        if let Ok(mut statuses) = self.statuses.lock() {
+            statuses.insert(status.server_id, status.clone());
+        }
+        let _ = app.emit("mcp-server-status", status);
+    }
+}
+
+impl Supervisor {
+    async fn shutdown(self) {
+        let _ = self.stop.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Starts every enabled server in the background. Called once at startup.
+pub fn start_enabled(app: &AppHandle) {
+    let servers = {
+        let db = app.state::<Database>();
+        let Ok(conn) = db.conn.lock() else {
+            return;
+        };
+        enabled_servers(&conn).unwrap_or_default()
+    };
+    
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mcp = app.state::<McpServers>();
+        for server in servers {
+            mcp.start(&app, server).await;
+        }
+    });
+}
+
+/// Keeps a server connected until `stop` fires: connects, registers its tools
+/// and restarts it with exponential backoff when it exits or stops answering.
+async fn supervise(app: AppHandle, server: McpServer, mut stop: oneshot::Receiver<()>) {
+    let mcp = app.state::<McpServers>();
+    let registry = app.state::<ToolRegistry>();
+    let prefix = tool_prefix(server.id);
+    let mut restarts = 0;
+    
+    let status = |state, tools: Vec<String>, error: Option<String>, restarts| McpServerStatus {
+        server_id: server.id,
+        state,
+        tools,
+        error,
+        restarts,
+    };
+    
+    loop {
+        mcp.report(&app, status(McpServerState::Starting, vec![], None, restarts));
+        let started = Instant::now();
+        
+        let error = tokio::select! {
+            error = run_session(&app, &server, restarts) => error,
+            _ = &mut stop => break,
+        };
+        registry.unregister_prefix(&prefix);
+        
+        if started.elapsed() >= STABLE_AFTER {
+            restarts = 0;
+        }
+        if restarts >= MAX_RESTARTS {
+            mcp.report(&app, status(McpServerState::Failed, vec![], Some(error), restarts));
+            let _ = (&mut stop).await;
+            break;
+        }
+        
+        restarts += 1;
+        mcp.report(&app, status(McpServerState::Failed, vec![], Some(error), restarts));
+        
+        let delay = Duration::from_secs(1 << restarts.min(5));
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = &mut stop => break,
+        }
+    }
+    
+    registry.unregister_prefix(&prefix);
+    mcp.report(&app, status(McpServerState::Stopped, vec![], None, restarts));
+}
+
+/// One connection to the server; returns why it ended.
+async fn run_session(app: &AppHandle, server: &McpServer, restarts: u32) -> String {
+    let mut session = match Session::connect(server).await {
+        Ok(session) => session,
+        Err(e) => return e,
+    };
+    
+    let remote_tools = match session.client.list_tools().await {
+        Ok(tools) => tools,
+        Err(e) => return e,
+    };
+    
+    let registry = app.state::<ToolRegistry>();
+    let mut names = Vec::new();
+    
+    for remote in remote_tools {
+        let name = tool_name(server.id, &remote.name);
+        let description = match remote.description.filter(|description| !description.is_empty()) {
+            Some(description) => format!("[{}] {}", server.name, description),
+            None => format!("[{}] {}", server.name, remote.name),
+        };
+        
+        registry.register(Arc::new(McpTool {
+            name: name.clone(),
+            description,
+            parameters: remote
+                .input_schema
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+            remote_name: remote.name,
+            require_approval: server.require_approval,
+            client: session.client.clone(),
+        }));
+        names.push(name);
+    }
+    
+    app.state::<McpServers>().report(
+        app,
+        McpServerStatus {
+            server_id: server.id,
+            state: McpServerState::Running,
+            tools: names,
+            error: None,
+            restarts,
+        },
+    );
+    
+    session.closed().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn tool_names_fit_the_api_limits() {
+        assert_eq!(tool_name(1, "get_weather"), "mcp_1_get_weather");
+        assert_eq!(tool_name(1, "get-weather2"), "mcp_1_get-weather2");
+        
+        let sanitized = tool_name(1, "files.read");
+        assert!(sanitized.starts_with("mcp_1_files_read_"));
+        assert_ne!(sanitized, tool_name(1, "files_read"));
+        
+        let long = tool_name(12, &"a".repeat(100));
+        assert_eq!(long.len(), MAX_TOOL_NAME);
+        assert!(long.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(long, tool_name(12, &"a".repeat(100)));
+        assert_eq!(tool_name(1, "ação").len(), "mcp_1_a__o_".len() + 8);
+    }
+    
+    #[test]
+    fn truncated_tool_names_do_not_collide() {
+        let shared = "search_".repeat(10);
+        let first = tool_name(1, &format!("{}issues", shared));
+        let second = tool_name(1, &format!("{}pull_requests", shared));
+        
+        assert_eq!(first.len(), MAX_TOOL_NAME);
+        assert_eq!(second.len(), MAX_TOOL_NAME);
+        assert_ne!(first, second);
+        assert_eq!(first[..55], second[..55]);
+    }
+    
+    #[test]
+    fn result_text_flattens_content_blocks() {
+        let result = json!({
+            "content": [
+                { "type": "text", "text": "Previsão: sol" },
+                { "type": "image", "data": "aGk=", "mimeType": "image/png" },
+                { "type": "resource", "resource": { "uri": "file:///a.txt", "text": "conteúdo de a" } },
+                { "type": "resource", "resource": { "uri": "file:///b.bin", "blob": "AA==" } },
+                { "type": "resource_link", "uri": "file:///c.txt", "name": "c" },
+                { "type": "novo", "value": 1 }
+            ]
+        });
+        
+        assert_eq!(
+            result_text(&result),
+            "Previsão: sol\n[image image/png]\nconteúdo de a\nfile:///b.bin\nfile:///c.txt\n{\"type\":\"novo\",\"value\":1}"
+        );
+    }
+    
+    #[test]
+    fn result_text_falls_back_to_structured_content() {
+        let result = json!({ "content": [], "structuredContent": { "temperature": 22 } });
+        
+        assert_eq!(result_text(&result), "{\"temperature\":22}");
+        assert_eq!(result_text(&json!({})), "");
+    }
+    
+    #[test]
+    fn find_response_matches_the_request_id() {
+        let response = json!({ "jsonrpc": "2.0", "id": 3, "result": {} });
+        
+        assert_eq!(find_response(response.clone(), 3), Some(response.clone()));
+        assert_eq!(find_response(response, 4), None);
+    }
+    
+    #[test]
+    fn find_response_searches_batches_and_skips_notifications() {
+        let batch = json!([
+            { "jsonrpc": "2.0", "method": "notifications/progress", "params": {} },
+            { "jsonrpc": "2.0", "id": 1, "result": { "first": true } },
+            { "jsonrpc": "2.0", "id": 2, "result": { "second": true } }
+        ]);
+        
+        assert_eq!(
+            find_response(batch.clone(), 2),
+            Some(json!({ "jsonrpc": "2.0", "id": 2, "result": { "second": true } }))
+        );
+        assert_eq!(find_response(batch, 9), None);
+        assert_eq!(find_response(json!({ "jsonrpc": "2.0", "method": "ping" }), 1), None);
+    }
+}
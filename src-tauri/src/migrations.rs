@@ -0,0 +1,642 @@
+use rusqlite::{Connection, Transaction};
+use std::path::{Path, PathBuf};
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Schema migrations, applied in order. Never edit a released migration;
+/// append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        up: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "stopped flag on messages",
+        up: messages_stopped,
+    },
+    Migration {
+        version: 3,
+        description: "generation parameters",
+        up: generation_params,
+    },
+    Migration {
+        version: 4,
+        description: "system prompts",
+        up: system_prompts,
+    },
+    Migration {
+        version: 5,
+        description: "conversation model binding",
+        up: conversation_model_binding,
+    },
+    Migration {
+        version: 6,
+        description: "provider profiles",
+        up: provider_profiles,
+    },
+    Migration {
+        version: 7,
+        description: "api key references",
+        up: api_key_references,
+    },
+    Migration {
+        version: 8,
+        description: "full-text search",
+        up: full_text_search,
+    },
+    Migration {
+        version: 9,
+        description: "message tree",
+        up: message_tree,
+    },
+    Migration {
+        version: 10,
+        description: "context window budget",
+        up: context_budget,
+    },
+    Migration {
+        version: 11,
+        description: "automatic titles",
+        up: auto_titles,
+    },
+    Migration {
+        version: 12,
+        description: "attachments",
+        up: attachments,
+    },
+    Migration {
+        version: 13,
+        description: "document attachments",
+        up: document_attachments,
+    },
+    Migration {
+        version: 14,
+        description: "knowledge bases",
+        up: knowledge_bases,
+    },
+    Migration {
+        version: 15,
+        description: "tool calling",
+        up: tool_calling,
+    },
+    Migration {
+        version: 16,
+        description: "mcp servers",
+        up: mcp_servers,
+    },
+    Migration {
+        version: 17,
+        description: "download queue",
+        up: downloads,
+    },
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Brings the database up to the latest schema version, backing it up first
+/// when an existing database is about to be upgraded.
+pub fn run(conn: &mut Connection, db_path: &Path) -> Result<(), String> {
+    let current: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let latest = latest_version();
+    
+    if current > latest {
+        return Err(format!(
+            "O banco de dados foi criado por uma versão mais nova do LLMpad (schema v{}, suportado até v{})",
+            current, latest
+        ));
+    }
+    
+    if current == latest {
+        return Ok(());
+    }
+    
+    if current > 0 || has_tables(conn)? {
+        backup(conn, db_path, current)?;
+    }
+    
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        
+        (migration.up)(&tx).map_err(|e| {
+            format!(
+                "Erro ao aplicar migração v{} ({}): {}",
+                migration.version, migration.description, e
+            )
+        })?;
+        
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+    
+    Ok(())
+}
+
+fn has_tables(conn: &Connection) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%')",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn database_file_name(db_path: &Path) -> String {
+    db_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "llmpad.db".to_string())
+}
+
+fn backup(conn: &Connection, db_path: &Path, version: i64) -> Result<(), String> {
+    let file_name = database_file_name(db_path);
+    let backup_path = db_path.with_file_name(format!("{}.v{}.bak", file_name, version));
+    
+    if backup_path.exists() {
+        std::fs::remove_file(&backup_path)
+            .map_err(|e| format!("Erro ao remover backup antigo: {}", e))?;
+    }
+    
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .map_err(|e| format!("Erro ao criar backup do banco de dados: {}", e))?;
+    
+    Ok(())
+}
+
+/// The pre-migration backups `run` has left next to the database.
+pub fn backups(db_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let prefix = format!("{}.v", database_file_name(db_path));
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Erro ao listar backups do banco de dados: {}", e)),
+    };
+    
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Erro ao listar backups do banco de dados: {}", e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && name.ends_with(".bak") {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort();
+    
+    Ok(backups)
+}
+
+fn add_column_if_missing(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+    
+    if !exists {
+        tx.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    
+    Ok(())
+}
+
+fn initial_schema(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS conversations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        
+        CREATE TABLE IF NOT EXISTS messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            conversation_id INTEGER NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+        );
+        
+        CREATE TABLE IF NOT EXISTS settings (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            api_url TEXT NOT NULL DEFAULT 'http://localhost:11434/v1',
+            api_key TEXT NOT NULL DEFAULT '',
+            model TEXT NOT NULL DEFAULT 'llama3.2'
+        );
+        
+        INSERT OR IGNORE INTO settings (id, api_url, api_key, model) VALUES (1, 'http://localhost:11434/v1', '', 'llama3.2');",
+    )
+}
+
+fn messages_stopped(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "messages", "stopped", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn generation_params(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE settings ADD COLUMN generation_params TEXT NOT NULL DEFAULT '{}';
+        ALTER TABLE conversations ADD COLUMN generation_params TEXT;",
+    )
+}
+
+fn system_prompts(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE settings ADD COLUMN system_prompt TEXT NOT NULL DEFAULT '';
+        ALTER TABLE conversations ADD COLUMN system_prompt TEXT;",
+    )
+}
+
+fn conversation_model_binding(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE conversations ADD COLUMN model TEXT;
+        ALTER TABLE conversations ADD COLUMN api_url TEXT;
+        ALTER TABLE messages ADD COLUMN model TEXT;",
+    )
+}
+
+fn provider_profiles(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE providers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL DEFAULT 'openai',
+            api_url TEXT NOT NULL,
+            api_key TEXT NOT NULL DEFAULT '',
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        
+        ALTER TABLE settings ADD COLUMN default_provider_id INTEGER REFERENCES providers(id) ON DELETE SET NULL;
+        ALTER TABLE conversations ADD COLUMN provider_id INTEGER REFERENCES providers(id) ON DELETE SET NULL;
+        
+        INSERT INTO providers (name, kind, api_url, api_key)
+            SELECT 'Padrão', 'ollama', api_url, api_key FROM settings WHERE id = 1;
+        
+        UPDATE settings SET default_provider_id = last_insert_rowid() WHERE id = 1;
+        
+        UPDATE conversations SET provider_id = (SELECT default_provider_id FROM settings WHERE id = 1)
+            WHERE api_url IS NULL OR api_url = (SELECT api_url FROM settings WHERE id = 1);",
+    )
+}
+
+fn api_key_references(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE providers ADD COLUMN api_key_ref TEXT;
+        ALTER TABLE settings ADD COLUMN api_key_ref TEXT;",
+    )
+}
+
+fn full_text_search(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE messages_fts USING fts5(
+            content,
+            content='messages',
+            content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2'
+        );
+        
+        CREATE VIRTUAL TABLE conversations_fts USING fts5(
+            title,
+            content='conversations',
+            content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2'
+        );
+        
+        CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+        
+        CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+        END;
+        
+        CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+            INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+        
+        CREATE TRIGGER conversations_fts_insert AFTER INSERT ON conversations BEGIN
+            INSERT INTO conversations_fts (rowid, title) VALUES (new.id, new.title);
+        END;
+        
+        CREATE TRIGGER conversations_fts_delete AFTER DELETE ON conversations BEGIN
+            INSERT INTO conversations_fts (conversations_fts, rowid, title) VALUES ('delete', old.id, old.title);
+        END;
+        
+        CREATE TRIGGER conversations_fts_update AFTER UPDATE OF title ON conversations BEGIN
+            INSERT INTO conversations_fts (conversations_fts, rowid, title) VALUES ('delete', old.id, old.title);
+            INSERT INTO conversations_fts (rowid, title) VALUES (new.id, new.title);
+        END;
+        
+        INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+        INSERT INTO conversations_fts (conversations_fts) VALUES ('rebuild');",
+    )
+}
+
+/// Messages so far form one chain per conversation: each message's parent is
+/// the one before it, and the last message is the current leaf.
+fn message_tree(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE messages ADD COLUMN parent_message_id INTEGER REFERENCES messages(id) ON DELETE CASCADE;
+        ALTER TABLE conversations ADD COLUMN current_message_id INTEGER REFERENCES messages(id) ON DELETE SET NULL;
+        
+        UPDATE messages SET parent_message_id = (
+            SELECT MAX(p.id) FROM messages p
+            WHERE p.conversation_id = messages.conversation_id AND p.id < messages.id
+        );
+        
+        UPDATE conversations SET current_message_id = (
+            SELECT MAX(m.id) FROM messages m WHERE m.conversation_id = conversations.id
+        );
+        
+        CREATE INDEX idx_messages_parent ON messages(parent_message_id);",
+    )
+}
+
+fn context_budget(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE settings ADD COLUMN context_length INTEGER NOT NULL DEFAULT 4096;
+        ALTER TABLE settings ADD COLUMN context_limits TEXT NOT NULL DEFAULT '{}';
+        ALTER TABLE settings ADD COLUMN context_strategy TEXT NOT NULL DEFAULT 'truncate';
+        ALTER TABLE conversations ADD COLUMN context_summary TEXT;
+        ALTER TABLE conversations ADD COLUMN context_summary_through INTEGER;",
+    )
+}
+
+fn auto_titles(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE settings ADD COLUMN auto_title INTEGER NOT NULL DEFAULT 1;")
+}
+
+fn attachments(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id INTEGER REFERENCES messages(id) ON DELETE CASCADE,
+            kind TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            data BLOB NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        
+        CREATE INDEX idx_attachments_message ON attachments(message_id);",
+    )
+}
+
+fn document_attachments(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE attachments ADD COLUMN text TEXT;")
+}
+
+fn knowledge_bases(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE knowledge_bases (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            source_path TEXT NOT NULL,
+            embedding_model TEXT NOT NULL,
+            provider_id INTEGER REFERENCES providers(id) ON DELETE SET NULL,
+            api_url TEXT,
+            indexed_at TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        
+        CREATE TABLE knowledge_chunks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            knowledge_base_id INTEGER NOT NULL REFERENCES knowledge_bases(id) ON DELETE CASCADE,
+            source TEXT NOT NULL,
+            chunk_index INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            embedding BLOB NOT NULL
+        );
+        
+        CREATE INDEX idx_knowledge_chunks_base ON knowledge_chunks(knowledge_base_id);
+        
+        CREATE TABLE conversation_knowledge_bases (
+            conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+            knowledge_base_id INTEGER NOT NULL REFERENCES knowledge_bases(id) ON DELETE CASCADE,
+            PRIMARY KEY (conversation_id, knowledge_base_id)
+        );",
+    )
+}
+
+fn tool_calling(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE settings ADD COLUMN tools_enabled INTEGER NOT NULL DEFAULT 0;")
+}
+
+fn mcp_servers(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE mcp_servers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            transport TEXT NOT NULL DEFAULT 'stdio',
+            command TEXT,
+            args TEXT NOT NULL DEFAULT '[]',
+            env TEXT NOT NULL DEFAULT '{}',
+            url TEXT,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            require_approval INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )
+}
+
+fn downloads(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE downloads (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            model TEXT NOT NULL,
+            provider_id INTEGER REFERENCES providers(id) ON DELETE SET NULL,
+            api_url TEXT,
+            state TEXT NOT NULL DEFAULT 'queued',
+            status TEXT,
+            completed INTEGER NOT NULL DEFAULT 0,
+            total INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        
+        CREATE INDEX idx_downloads_state ON downloads(state);
+        
+        ALTER TABLE settings ADD COLUMN max_concurrent_downloads INTEGER NOT NULL DEFAULT 1;",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Path of a database in an empty temporary folder. The tests keep the
+    /// connection in memory; only backups are written to disk.
+    fn database_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("llmpad-migrations-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("llmpad.db")
+    }
+    
+    /// A database as an older release left it, migrated up to `version`.
+    fn database_at(version: i64) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= version) {
+            let tx = conn.transaction().unwrap();
+            (migration.up)(&tx).unwrap();
+            tx.pragma_update(None, "user_version", migration.version).unwrap();
+            tx.commit().unwrap();
+        }
+        
+        conn
+    }
+    
+    fn user_version(conn: &Connection) -> i64 {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+    }
+    
+    fn insert_message(conn: &Connection, role: &str, content: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO messages (conversation_id, role, content) VALUES (1, ?1, ?2)",
+            [role, content],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+    
+    fn parent_of(conn: &Connection, message_id: i64) -> Option<i64> {
+        conn.query_row(
+            "SELECT parent_message_id FROM messages WHERE id = ?1",
+            [message_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+    
+    fn current_message(conn: &Connection) -> Option<i64> {
+        conn.query_row("SELECT current_message_id FROM conversations WHERE id = 1", [], |row| row.get(0))
+            .unwrap()
+    }
+    
+    #[test]
+    fn versions_are_consecutive() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i64 + 1, "{}", migration.description);
+        }
+    }
+    
+    #[test]
+    fn new_database_is_created_at_the_latest_version_without_a_backup() {
+        let path = database_path("new");
+        let mut conn = Connection::open_in_memory().unwrap();
+        
+        run(&mut conn, &path).unwrap();
+        
+        assert_eq!(user_version(&conn), latest_version());
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 0);
+    }
+    
+    #[test]
+    fn database_from_before_migrations_is_backed_up_and_upgraded() {
+        let path = database_path("unversioned");
+        let mut conn = database_at(1);
+        conn.pragma_update(None, "user_version", 0).unwrap();
+        conn.execute("INSERT INTO conversations (title) VALUES ('Antiga')", []).unwrap();
+        insert_message(&conn, "user", "Olá");
+        
+        run(&mut conn, &path).unwrap();
+        
+        assert_eq!(user_version(&conn), latest_version());
+        assert!(path.with_file_name("llmpad.db.v0.bak").exists());
+        let title: String = conn
+            .query_row("SELECT title FROM conversations WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(title, "Antiga");
+    }
+    
+    #[test]
+    fn older_database_keeps_its_messages_as_a_chain() {
+        let path = database_path("older");
+        let mut conn = database_at(8);
+        conn.execute("INSERT INTO conversations (title) VALUES ('Conversa')", []).unwrap();
+        let question = insert_message(&conn, "user", "Quanto é 2 + 2?");
+        let answer = insert_message(&conn, "assistant", "4");
+        let follow_up = insert_message(&conn, "user", "E 3 + 3?");
+        
+        run(&mut conn, &path).unwrap();
+        
+        assert_eq!(user_version(&conn), latest_version());
+        assert!(path.with_file_name("llmpad.db.v8.bak").exists());
+        assert_eq!(parent_of(&conn, question), None);
+        assert_eq!(parent_of(&conn, answer), Some(question));
+        assert_eq!(parent_of(&conn, follow_up), Some(answer));
+        assert_eq!(current_message(&conn), Some(follow_up));
+        
+        let matches: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'Quanto'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matches, 1);
+    }
+    
+    #[test]
+    fn database_from_a_newer_release_is_refused() {
+        let path = database_path("newer");
+        let mut conn = database_at(latest_version());
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+        
+        assert!(run(&mut conn, &path).is_err());
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 0);
+    }
+    
+    #[test]
+    fn backups_lose_their_plaintext_keys_once_the_keys_are_moved() {
+        let path = database_path("scrub-backups");
+        let mut conn = database_at(5);
+        conn.execute("UPDATE settings SET api_key = 'sk-plaintext-secret' WHERE id = 1", []).unwrap();
+        
+        run(&mut conn, &path).unwrap();
+        let backups = backups(&path).unwrap();
+        assert_eq!(backups, vec![path.with_file_name("llmpad.db.v5.bak")]);
+        assert!(String::from_utf8_lossy(&std::fs::read(&backups[0]).unwrap()).contains("sk-plaintext-secret"));
+        
+        crate::secrets::migrate_plaintext_keys(&conn, &crate::secrets::MemoryStore::default()).unwrap();
+        crate::secrets::scrub_backups(&path).unwrap();
+        
+        let backup = Connection::open(&backups[0]).unwrap();
+        let api_key: String = backup
+            .query_row("SELECT api_key FROM settings WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(api_key, "");
+        drop(backup);
+        assert!(!String::from_utf8_lossy(&std::fs::read(&backups[0]).unwrap()).contains("sk-plaintext-secret"));
+    }
+}
@@ -0,0 +1,711 @@
+use serde::{Deserialize, Serialize};
+
+/// Value type of each PARAMETER accepted by Ollama.
+const PARAMETERS: &[(&str, ParameterKind)] = &[
+    ("num_ctx", ParameterKind::Integer),
+    ("num_batch", ParameterKind::Integer),
+    ("num_gpu", ParameterKind::Integer),
+    ("main_gpu", ParameterKind::Integer),
+    ("num_thread", ParameterKind::Integer),
+    ("num_keep", ParameterKind::Integer),
+    ("num_predict", ParameterKind::Integer),
+    ("repeat_last_n", ParameterKind::Integer),
+    ("seed", ParameterKind::Integer),
+    ("top_k", ParameterKind::Integer),
+    ("mirostat", ParameterKind::Integer),
+    ("temperature", ParameterKind::Float),
+    ("top_p", ParameterKind::Float),
+    ("min_p", ParameterKind::Float),
+    ("typical_p", ParameterKind::Float),
+    ("tfs_z", ParameterKind::Float),
+    ("repeat_penalty", ParameterKind::Float),
+    ("presence_penalty", ParameterKind::Float),
+    ("frequency_penalty", ParameterKind::Float),
+    ("mirostat_tau", ParameterKind::Float),
+    ("mirostat_eta", ParameterKind::Float),
+    ("penalize_newline", ParameterKind::Bool),
+    ("use_mmap", ParameterKind::Bool),
+    ("use_mlock", ParameterKind::Bool),
+    ("numa", ParameterKind::Bool),
+    ("low_vram", ParameterKind::Bool),
+    ("vocab_only", ParameterKind::Bool),
+    ("stop", ParameterKind::String),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParameterKind {
+    Integer,
+    Float,
+    Bool,
+    String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ParameterValue {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+    System,
+    User,
+    Assistant,
+}
+
+impl MessageRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageRole::System => "system",
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "instruction", rename_all = "snake_case")]
+pub enum Instruction {
+    From { model: String },
+    Parameter { name: String, value: ParameterValue },
+    Template { template: String },
+    System { system: String },
+    Adapter { path: String },
+    License { license: String },
+    Message { role: MessageRole, content: String },
+}
+
+/// An instruction and the 1-based position of its keyword.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Statement {
+    #[serde(flatten)]
+    pub instruction: Instruction,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found while parsing, at a 1-based line and column.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "linha {}, coluna {}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ModelParameter {
+    pub name: String,
+    pub value: ParameterValue,
+}
+
+/// A parsed Modelfile. Statements with errors are left out, so `statements`
+/// only holds instructions that can be sent to Ollama as they are.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Modelfile {
+    pub statements: Vec<Statement>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Modelfile {
+    pub fn from(&self) -> Option<&str> {
+        self.statements.iter().find_map(|statement| match &statement.instruction {
+            Instruction::From { model } => Some(model.as_str()),
+            _ => None,
+        })
+    }
+    
+    pub fn parameters(&self) -> Vec<ModelParameter> {
+        self.statements
+            .iter()
+            .filter_map(|statement| match &statement.instruction {
+                Instruction::Parameter { name, value } => Some(ModelParameter {
+                    name: name.clone(),
+                    value: value.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+    
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl std::fmt::Display for ParameterValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParameterValue::Integer(value) => write!(f, "{}", value),
+            ParameterValue::Float(value) => write!(f, "{}", value),
+            ParameterValue::Bool(value) => write!(f, "{}", value),
+            ParameterValue::String(value) => f.write_str(&quote(value, true)),
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::From { model } => write!(f, "FROM {}", quote(model, false)),
+            Instruction::Parameter { name, value } => write!(f, "PARAMETER {} {}", name, value),
+            Instruction::Template { template } => write!(f, "TEMPLATE {}", quote(template, true)),
+            Instruction::System { system } => write!(f, "SYSTEM {}", quote(system, true)),
+            Instruction::Adapter { path } => write!(f, "ADAPTER {}", quote(path, false)),
+            Instruction::License { license } => write!(f, "LICENSE {}", quote(license, true)),
+            Instruction::Message { role, content } => {
+                write!(f, "MESSAGE {} {}", role.as_str(), quote(content, true))
+            }
+        }
+    }
+}
+
+/// Writes instructions as a Modelfile, one per line, quoting values so that
+/// `parse` reads them back unchanged. The one exception is `\r\n` inside a
+/// value, which comes back as `\n` like any other line break (see `parse`).
+pub fn render(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .map(|instruction| format!("{}\n", instruction))
+        .collect()
+}
+
+/// Quotes a value when written bare it would not be read back as it is, or
+/// always when `always` is set. Multi-line values use `"""` when they can.
+fn quote(value: &str, always: bool) -> String {
+    let bare = !value.is_empty()
+        && !value.contains('\n')
+        && !value.starts_with('"')
+        && value.trim() == value;
+    if bare && !always {
+        return value.to_string();
+    }
+    
+    if value.contains('\n') && !value.contains("\"\"\"") && !value.ends_with('"') {
+        format!("\"\"\"{}\"\"\"", value)
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Parses a Modelfile. Never fails: problems are reported as diagnostics and
+/// parsing continues with the next instruction. Lines may end in `\n` or
+/// `\r\n`; multi-line values always use `\n`, so a file saved on Windows
+/// reads the same as one saved elsewhere.
+pub fn parse(content: &str) -> Modelfile {
+    let mut parser = Parser {
+        lines: content.lines().collect(),
+        index: 0,
+        modelfile: Modelfile::default(),
+    };
+    
+    while parser.index < parser.lines.len() {
+        parser.statement();
+    }
+    
+    if parser.modelfile.from().is_none() && parser.modelfile.errors().next().is_none() {
+        parser.error(1, 1, "a instrução FROM é obrigatória".to_string());
+    }
+    
+    parser.modelfile
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    index: usize,
+    modelfile: Modelfile,
+}
+
+/// A word of the current line with its 1-based column.
+struct Word<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&mut self, line: usize, column: usize, message: String) {
+        self.diagnostic(line, column, Severity::Error, message);
+    }
+    
+    fn diagnostic(&mut self, line: usize, column: usize, severity: Severity, message: String) {
+        self.modelfile.diagnostics.push(Diagnostic {
+            line,
+            column,
+            severity,
+            message,
+        });
+    }
+    
+    fn statement(&mut self) {
+        let line_number = self.index + 1;
+        let line = self.lines[self.index];
+        self.index += 1;
+        
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return;
+        }
+        
+        let mut offset = line.len() - trimmed.len();
+        let keyword = next_word(line, &mut offset);
+        let name = keyword.text.to_uppercase();
+        
+        let has_key = match name.as_str() {
+            "FROM" | "TEMPLATE" | "SYSTEM" | "ADAPTER" | "LICENSE" => false,
+            "PARAMETER" | "MESSAGE" => true,
+            _ => {
+                self.error(
+                    line_number,
+                    keyword.column,
+                    format!("instrução desconhecida: {}", keyword.text),
+                );
+                self.skip_value(line, offset);
+                return;
+            }
+        };
+        
+        let key = if has_key {
+            let key = next_word(line, &mut offset);
+            if key.text.is_empty() {
+                self.error(line_number, keyword.column, format!("{} sem argumentos", name));
+                return;
+            }
+            Some(key)
+        } else {
+            None
+        };
+        
+        let value_column = column_of(line, offset);
+        let quoted = line[offset..].starts_with('"');
+        let Some(value) = self.value(line_number, line, offset) else {
+            return;
+        };
+        if value.trim().is_empty() && !quoted {
+            self.error(line_number, keyword.column, format!("{} sem valor", name));
+            return;
+        }
+        
+        let instruction = match (name.as_str(), key) {
+            ("FROM", _) => Instruction::From { model: value },
+            ("TEMPLATE", _) => Instruction::Template { template: value },
+            ("SYSTEM", _) => Instruction::System { system: value },
+            ("ADAPTER", _) => Instruction::Adapter { path: value },
+            ("LICENSE", _) => Instruction::License { license: value },
+            ("PARAMETER", Some(key)) => {
+                let Some(value) = self.parameter(line_number, &key, &value, value_column) else {
+                    return;
+                };
+                Instruction::Parameter {
+                    name: key.text.to_lowercase(),
+                    value,
+                }
+            }
+            ("MESSAGE", Some(key)) => {
+                let role = match key.text.to_lowercase().as_str() {
+                    "system" => MessageRole::System,
+                    "user" => MessageRole::User,
+                    "assistant" => MessageRole::Assistant,
+                    _ => {
+                        self.error(
+                            line_number,
+                            key.column,
+                            format!("papel de mensagem inválido: {} (use system, user ou assistant)", key.text),
+                        );
+                        return;
+                    }
+                };
+                Instruction::Message { role, content: value }
+            }
+            _ => return,
+        };
+        
+        if matches!(instruction, Instruction::From { .. }) && self.modelfile.from().is_some() {
+            self.diagnostic(
+                line_number,
+                keyword.column,
+                Severity::Warning,
+                "FROM repetido; apenas o primeiro é usado".to_string(),
+            );
+            return;
+        }
+        
+        self.modelfile.statements.push(Statement {
+            instruction,
+            line: line_number,
+            column: keyword.column,
+        });
+    }
+    
+    fn parameter(&mut self, line: usize, key: &Word, value: &str, column: usize) -> Option<ParameterValue> {
+        let name = key.text.to_lowercase();
+        let Some((_, kind)) = PARAMETERS.iter().find(|(known, _)| *known == name) else {
+            self.error(line, key.column, format!("parâmetro desconhecido: {}", key.text));
+            return None;
+        };
+        
+        let value = value.trim();
+        let parsed = match kind {
+            ParameterKind::Integer => value.parse().ok().map(ParameterValue::Integer),
+            ParameterKind::Float => value.parse().ok().map(ParameterValue::Float),
+            ParameterKind::Bool => match value.to_lowercase().as_str() {
+                "true" => Some(ParameterValue::Bool(true)),
+                "false" => Some(ParameterValue::Bool(false)),
+                _ => None,
+            },
+            ParameterKind::String => Some(ParameterValue::String(value.to_string())),
+        };
+        
+        if parsed.is_none() {
+            let expected = match kind {
+                ParameterKind::Integer => "um número inteiro",
+                ParameterKind::Float => "um número",
+                ParameterKind::Bool => "true ou false",
+                ParameterKind::String => "um texto",
+            };
+            self.error(
+                line,
+                column,
+                format!("valor inválido para {}: \"{}\" (esperado {})", name, value, expected),
+            );
+        }
+        
+        parsed
+    }
+    
+    /// Reads the value starting at byte `offset` of `line`: the rest of the
+    /// line, or a `"..."` / `"""..."""` string that may continue on the
+    /// following lines.
+    fn value(&mut self, line_number: usize, line: &str, offset: usize) -> Option<String> {
+        let rest = &line[offset..];
+        let column = column_of(line, offset);
+        
+        let (delimiter, body) = if let Some(body) = rest.strip_prefix("\"\"\"") {
+            ("\"\"\"", body)
+        } else if let Some(body) = rest.strip_prefix('"') {
+            ("\"", body)
+        } else {
+            return Some(rest.trim_end().to_string());
+        };
+        
+        let mut value = String::new();
+        let mut current = body;
+        let mut current_line = line_number;
+        let mut current_text = line;
+        
+        loop {
+            if let Some(end) = find_closing(current, delimiter) {
+                value.push_str(&unescape(&current[..end], delimiter));
+                
+                let trailing = current[end + delimiter.len()..].trim();
+                if !trailing.is_empty() {
+                    let trailing_offset = current_text.len() - current[end + delimiter.len()..].trim_start().len();
+                    self.diagnostic(
+                        current_line,
+                        column_of(current_text, trailing_offset),
+                        Severity::Warning,
+                        "texto após as aspas ignorado".to_string(),
+                    );
+                }
+                return Some(value);
+            }
+            
+            value.push_str(&unescape(current, delimiter));
+            
+            if self.index >= self.lines.len() {
+                self.error(line_number, column, "string não terminada".to_string());
+                return None;
+            }
+            
+            value.push('\n');
+            current_text = self.lines[self.index];
+            current = current_text;
+            self.index += 1;
+            current_line = self.index;
+        }
+    }
+    
+    /// Consumes the value of an unknown instruction so that a multi-line
+    /// string does not produce one diagnostic per line.
+    fn skip_value(&mut self, line: &str, offset: usize) {
+        let rest = &line[offset..];
+        let delimiter = if rest.starts_with("\"\"\"") {
+            "\"\"\""
+        } else if rest.starts_with('"') {
+            "\""
+        } else {
+            return;
+        };
+        
+        if find_closing(&rest[delimiter.len()..], delimiter).is_some() {
+            return;
+        }
+        while self.index < self.lines.len() {
+            let line = self.lines[self.index];
+            self.index += 1;
+            if find_closing(line, delimiter).is_some() {
+                return;
+            }
+        }
+    }
+}
+
+fn next_word<'a>(line: &'a str, offset: &mut usize) -> Word<'a> {
+    let rest = &line[*offset..];
+    let start = *offset + (rest.len() - rest.trim_start().len());
+    let rest = &line[start..];
+    let end = start + rest.find(char::is_whitespace).unwrap_or(rest.len());
+    
+    let word = Word {
+        text: &line[start..end],
+        column: column_of(line, start),
+    };
+    
+    let after = &line[end..];
+    *offset = end + (after.len() - after.trim_start().len());
+    word
+}
+
+fn column_of(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+/// Position of the closing delimiter, skipping `\"` escapes in single-quoted
+/// strings.
+fn find_closing(text: &str, delimiter: &str) -> Option<usize> {
+    if delimiter != "\"" {
+        return text.find(delimiter);
+    }
+    
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(index),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(text: &str, delimiter: &str) -> String {
+    if delimiter == "\"" {
+        text.replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn instructions(modelfile: &Modelfile) -> Vec<Instruction> {
+        modelfile.statements.iter().map(|statement| statement.instruction.clone()).collect()
+    }
+    
+    fn messages(modelfile: &Modelfile) -> Vec<(usize, usize, Severity, &str)> {
+        modelfile
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.severity, d.message.as_str()))
+            .collect()
+    }
+    
+    #[test]
+    fn parses_instructions_with_positions() {
+        let modelfile = parse("# comentário\nFROM llama3:8b\n\n  parameter Temperature 0.7\nMESSAGE user Oi\n");
+        
+        assert!(modelfile.diagnostics.is_empty());
+        assert_eq!(
+            modelfile.statements,
+            vec![
+                Statement {
+                    instruction: Instruction::From { model: "llama3:8b".to_string() },
+                    line: 2,
+                    column: 1,
+                },
+                Statement {
+                    instruction: Instruction::Parameter {
+                        name: "temperature".to_string(),
+                        value: ParameterValue::Float(0.7),
+                    },
+                    line: 4,
+                    column: 3,
+                },
+                Statement {
+                    instruction: Instruction::Message {
+                        role: MessageRole::User,
+                        content: "Oi".to_string(),
+                    },
+                    line: 5,
+                    column: 1,
+                },
+            ]
+        );
+    }
+    
+    #[test]
+    fn unescapes_double_quoted_values() {
+        let modelfile = parse("FROM llama3\nSYSTEM \"Diga \\\"olá\\\" em C:\\\\temp\"\n");
+        
+        assert_eq!(
+            instructions(&modelfile)[1],
+            Instruction::System { system: "Diga \"olá\" em C:\\temp".to_string() }
+        );
+    }
+    
+    #[test]
+    fn reads_triple_quoted_values_over_several_lines() {
+        let modelfile = parse("FROM llama3\nTEMPLATE \"\"\"{{ .System }}\n\n{{ .Prompt }} \"sem\" \\escape\"\"\"\nSYSTEM x\n");
+        
+        assert!(modelfile.diagnostics.is_empty());
+        assert_eq!(
+            instructions(&modelfile)[1..],
+            [
+                Instruction::Template { template: "{{ .System }}\n\n{{ .Prompt }} \"sem\" \\escape".to_string() },
+                Instruction::System { system: "x".to_string() },
+            ]
+        );
+        assert_eq!(modelfile.statements[2].line, 5);
+    }
+    
+    #[test]
+    fn accepts_quoted_empty_values_only() {
+        let modelfile = parse("FROM llama3\nSYSTEM \"\"\nLICENSE \"\"\"\"\"\"\nTEMPLATE\n");
+        
+        assert_eq!(
+            instructions(&modelfile)[1..],
+            [
+                Instruction::System { system: String::new() },
+                Instruction::License { license: String::new() },
+            ]
+        );
+        assert_eq!(messages(&modelfile), vec![(4, 1, Severity::Error, "TEMPLATE sem valor")]);
+    }
+    
+    #[test]
+    fn reports_unknown_instructions_and_skips_their_values() {
+        let modelfile = parse("FROM llama3\nINCLUDE \"\"\"a\nb\"\"\"\nSYSTEM ok\n");
+        
+        assert_eq!(messages(&modelfile), vec![(2, 1, Severity::Error, "instrução desconhecida: INCLUDE")]);
+        assert_eq!(instructions(&modelfile)[1], Instruction::System { system: "ok".to_string() });
+    }
+    
+    #[test]
+    fn reports_invalid_parameters_and_roles() {
+        let modelfile = parse("FROM llama3\nPARAMETER num_ctx muito\nPARAMETER colour red\nMESSAGE tool oi\n");
+        
+        assert_eq!(
+            messages(&modelfile),
+            vec![
+                (2, 19, Severity::Error, "valor inválido para num_ctx: \"muito\" (esperado um número inteiro)"),
+                (3, 11, Severity::Error, "parâmetro desconhecido: colour"),
+                (4, 9, Severity::Error, "papel de mensagem inválido: tool (use system, user ou assistant)"),
+            ]
+        );
+        assert_eq!(modelfile.statements.len(), 1);
+    }
+    
+    #[test]
+    fn requires_from() {
+        let modelfile = parse("SYSTEM sem modelo\n");
+        
+        assert_eq!(modelfile.from(), None);
+        assert_eq!(messages(&modelfile), vec![(1, 1, Severity::Error, "a instrução FROM é obrigatória")]);
+    }
+    
+    #[test]
+    fn warns_about_repeated_from_and_trailing_text() {
+        let modelfile = parse("FROM llama3\nFROM mistral\nSYSTEM \"oi\" extra\n");
+        
+        assert_eq!(modelfile.from(), Some("llama3"));
+        assert_eq!(
+            messages(&modelfile),
+            vec![
+                (2, 1, Severity::Warning, "FROM repetido; apenas o primeiro é usado"),
+                (3, 13, Severity::Warning, "texto após as aspas ignorado"),
+            ]
+        );
+        assert_eq!(modelfile.errors().count(), 0);
+    }
+    
+    #[test]
+    fn reports_unterminated_strings_at_their_start() {
+        let modelfile = parse("FROM llama3\nSYSTEM \"\"\"nunca\nfecha\n");
+        
+        assert_eq!(messages(&modelfile), vec![(2, 8, Severity::Error, "string não terminada")]);
+    }
+    
+    #[test]
+    fn crlf_files_parse_like_lf_files() {
+        let lf = "FROM llama3\nSYSTEM \"\"\"a\nb\"\"\"\n";
+        
+        assert_eq!(instructions(&parse(&lf.replace('\n', "\r\n"))), instructions(&parse(lf)));
+    }
+    
+    #[test]
+    fn render_normalises_crlf_inside_values() {
+        let rendered = render(&[
+            Instruction::From { model: "llama3".to_string() },
+            Instruction::System { system: "a\r\nb\rc".to_string() },
+        ]);
+        
+        assert_eq!(
+            instructions(&parse(&rendered))[1],
+            Instruction::System { system: "a\nb\rc".to_string() }
+        );
+    }
+    
+    #[test]
+    fn render_round_trips_through_parse() {
+        let values = [
+            "simples",
+            " espaços nas pontas ",
+            "",
+            "\"começa com aspas",
+            "termina com barra\\",
+            "barra e aspas \\\" juntas \\\\\"",
+            "várias\nlinhas",
+            "várias\nlinhas terminando em \"",
+            "contém \"\"\" e\nquebra de linha",
+            "\n",
+        ];
+        
+        let mut instructions = vec![
+            Instruction::From { model: "./pesos do modelo.gguf".to_string() },
+            Instruction::Adapter { path: "/opt/lora".to_string() },
+            Instruction::Parameter { name: "num_ctx".to_string(), value: ParameterValue::Integer(4096) },
+            Instruction::Parameter { name: "top_p".to_string(), value: ParameterValue::Float(0.9) },
+            Instruction::Parameter { name: "use_mmap".to_string(), value: ParameterValue::Bool(false) },
+            Instruction::Parameter { name: "stop".to_string(), value: ParameterValue::String("<|end|>".to_string()) },
+        ];
+        for value in values {
+            instructions.push(Instruction::System { system: value.to_string() });
+            instructions.push(Instruction::Message { role: MessageRole::Assistant, content: value.to_string() });
+        }
+        
+        let parsed = parse(&render(&instructions));
+        
+        assert!(parsed.diagnostics.is_empty(), "{:?}", parsed.diagnostics);
+        assert_eq!(self::instructions(&parsed), instructions);
+    }
+}
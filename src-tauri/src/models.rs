@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::modelfile::{Diagnostic, ModelParameter};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Conversation {
@@ -6,6 +8,12 @@ pub struct Conversation {
     pub title: String,
     pub created_at: String,
     pub updated_at: String,
+    pub generation_params: Option<GenerationParams>,
+    pub system_prompt: Option<String>,
+    pub model: Option<String>,
+    pub api_url: Option<String>,
+    pub provider_id: Option<i64>,
+    pub current_message_id: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -15,19 +23,266 @@ pub struct Message {
     pub role: String,
     pub content: String,
     pub created_at: String,
+    pub stopped: bool,
+    pub model: Option<String>,
+    pub parent_message_id: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppSettings {
     pub api_url: String,
-    pub api_key: String,
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub has_api_key: bool,
     pub model: String,
+    #[serde(default)]
+    pub generation_params: GenerationParams,
+    #[serde(default)]
+    pub system_prompt: String,
+    #[serde(default)]
+    pub default_provider_id: Option<i64>,
+    #[serde(default = "default_context_length")]
+    pub context_length: u32,
+    #[serde(default)]
+    pub context_limits: HashMap<String, u32>,
+    #[serde(default)]
+    pub context_strategy: ContextStrategy,
+    #[serde(default = "default_auto_title")]
+    pub auto_title: bool,
+    #[serde(default)]
+    pub tools_enabled: bool,
+    #[serde(default = "default_max_concurrent_downloads")]
+    pub max_concurrent_downloads: u32,
+    #[serde(default, skip_deserializing)]
+    pub secrets_error: Option<String>,
+}
+
+fn default_context_length() -> u32 {
+    crate::context::DEFAULT_CONTEXT_LENGTH
+}
+
+fn default_auto_title() -> bool {
+    true
+}
+
+fn default_max_concurrent_downloads() -> u32 {
+    1
+}
+
+/// What happens to the oldest turns once a conversation outgrows the
+/// model's context window.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextStrategy {
+    #[default]
+    Truncate,
+    Summarize,
+}
+
+impl ContextStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContextStrategy::Truncate => "truncate",
+            ContextStrategy::Summarize => "summarize",
+        }
+    }
+    
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "summarize" => ContextStrategy::Summarize,
+            _ => ContextStrategy::Truncate,
+        }
+    }
+}
+
+/// How the history of a turn was fitted into the context window.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ContextReport {
+    pub context_length: u32,
+    pub estimated_tokens: u32,
+    pub dropped_message_ids: Vec<i64>,
+    pub summarized: bool,
+    pub truncated_attachment_ids: Vec<i64>,
+    pub sources: Vec<Citation>,
+}
+
+/// Knowledge base chunk added to a turn, numbered as cited in the prompt.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Citation {
+    pub index: usize,
+    pub knowledge_base_id: i64,
+    pub chunk_id: i64,
+    pub source: String,
+    pub score: f32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KnowledgeBase {
+    pub id: i64,
+    pub name: String,
+    pub source_path: String,
+    pub embedding_model: String,
+    pub provider_id: Option<i64>,
+    pub api_url: Option<String>,
+    pub chunk_count: i64,
+    pub indexed_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IngestReport {
+    pub knowledge_base_id: i64,
+    pub files: usize,
+    pub chunks: usize,
+    pub skipped_files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IngestProgressEvent {
+    pub knowledge_base_id: i64,
+    pub processed_files: usize,
+    pub total_files: usize,
+    pub chunks: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Provider {
+    pub id: i64,
+    pub name: String,
+    pub kind: String,
+    pub api_url: String,
+    #[serde(skip)]
+    pub api_key_ref: Option<String>,
+    /// Key still stored in plaintext because moving it to the secret store
+    /// failed. Empty once it has been moved.
+    #[serde(skip)]
+    pub api_key: String,
+    pub has_api_key: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProviderInput {
+    pub name: String,
+    pub kind: String,
+    pub api_url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GenerationParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ChatMessage {
     pub role: String,
-    pub content: String,
+    pub content: MessageContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+/// Plain text, or the content-part array used to send images to
+/// multimodal models.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// Text of the message, with image parts left out.
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+    
+    /// Appends text to the message, after the first text part when there
+    /// are images.
+    pub fn push_text(&mut self, extra: &str) {
+        match self {
+            MessageContent::Text(text) => text.push_str(extra),
+            MessageContent::Parts(parts) => match parts.iter_mut().find_map(|part| match part {
+                ContentPart::Text { text } => Some(text),
+                ContentPart::ImageUrl { .. } => None,
+            }) {
+                Some(text) => text.push_str(extra),
+                None => parts.insert(0, ContentPart::Text { text: extra.to_string() }),
+            },
+        }
+    }
+    
+    pub fn image_count(&self) -> usize {
+        match self {
+            MessageContent::Text(_) => 0,
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter(|part| matches!(part, ContentPart::ImageUrl { .. }))
+                .count(),
+        }
+    }
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+/// File attached to a message. The data itself is only loaded when the
+/// history is sent to the model or shown in the UI.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: i64,
+    pub message_id: Option<i64>,
+    pub kind: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub created_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,6 +290,57 @@ pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub stream: bool,
+    #[serde(flatten)]
+    pub params: GenerationParams,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+}
+
+/// Function advertised to the model, in the OpenAI `tools` format.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolCall {
+    #[serde(default)]
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_kind")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+fn default_tool_kind() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON-encoded arguments. Some servers send an object instead of a
+    /// string; it is re-encoded so history always uses the string form.
+    #[serde(deserialize_with = "string_or_json")]
+    pub arguments: String,
+}
+
+fn string_or_json<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(text) => text,
+        value => value.to_string(),
+    })
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,7 +355,203 @@ pub struct ChatChoice {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatMessageResponse {
-    pub content: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatStreamChunk {
+    pub choices: Vec<ChatStreamChoice>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatStreamChoice {
+    pub delta: ChatStreamDelta,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatStreamDelta {
+    pub content: Option<String>,
+}
+
+/// Deltas are emitted before the reply is saved, so they are keyed by the
+/// user message being answered; `ChatDoneEvent::message` is the saved reply.
+#[derive(Debug, Serialize, Clone)]
+pub struct ChatDeltaEvent {
+    pub conversation_id: i64,
+    pub user_message_id: i64,
+    pub delta: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ChatDoneEvent {
+    pub conversation_id: i64,
+    pub user_message_id: i64,
+    pub message: Message,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ConversationTitleEvent {
+    pub conversation_id: i64,
+    pub title: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolCallEvent {
+    pub conversation_id: i64,
+    pub name: String,
+    pub arguments: String,
+    pub result: String,
+    pub is_error: bool,
+}
+
+/// Sent when a tool that requires approval is about to run; answered with
+/// `respond_tool_approval`.
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolApprovalRequest {
+    pub id: u64,
+    pub conversation_id: i64,
+    pub name: String,
+    pub arguments: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum McpTransport {
+    #[default]
+    Stdio,
+    Http,
+}
+
+impl McpTransport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            McpTransport::Stdio => "stdio",
+            McpTransport::Http => "http",
+        }
+    }
+    
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "http" => McpTransport::Http,
+            _ => McpTransport::Stdio,
+        }
+    }
+}
+
+/// An MCP server, launched as a local process (`stdio`) or reached over
+/// streamable HTTP (`http`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct McpServer {
+    pub id: i64,
+    pub name: String,
+    pub transport: McpTransport,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub url: Option<String>,
+    pub enabled: bool,
+    pub require_approval: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct McpServerInput {
+    pub name: String,
+    pub transport: McpTransport,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub require_approval: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum McpServerState {
+    Stopped,
+    Starting,
+    Running,
+    Failed,
+}
+
+/// Connection state of an MCP server, emitted as `mcp-server-status` whenever
+/// it changes. `tools` holds the names exposed to models.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct McpServerStatus {
+    pub server_id: i64,
+    pub state: McpServerState,
+    pub tools: Vec<String>,
+    pub error: Option<String>,
+    pub restarts: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchHit {
+    pub conversation_id: i64,
+    pub conversation_title: String,
+    pub message_id: Option<i64>,
+    pub role: Option<String>,
+    pub snippet: String,
+    /// bm25 score; only comparable between hits of the same kind.
+    pub rank: f64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportDocument {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub conversations: Vec<ExportedConversation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportedConversation {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    pub messages: Vec<Message>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    Chatgpt,
+    OpenWebui,
+    Llmpad,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportReport {
+    pub source: ImportSource,
+    pub dry_run: bool,
+    pub conversations: usize,
+    pub messages: usize,
+    pub skipped_messages: usize,
+    pub conversation_ids: Vec<i64>,
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -66,6 +568,20 @@ pub struct ModelFileInfo {
     pub content: String,
     pub base_model: Option<String>,
     pub is_base_available: bool,
+    pub parameters: Vec<ModelParameter>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The fields of a Modelfile built from a form instead of raw text.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModelfileFields {
+    pub base_model: String,
+    #[serde(default)]
+    pub parameters: Vec<ModelParameter>,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,3 +593,86 @@ pub struct OllamaModel {
 pub struct OllamaListResponse {
     pub models: Vec<OllamaModel>,
 }
+
+/// Progress of `pull_ollama_model`, emitted as `ollama-pull-progress`.
+/// Byte counts, speed and ETA refer to the layer being downloaded (`digest`)
+/// and are absent for steps such as "verifying sha256 digest".
+#[derive(Debug, Serialize, Clone)]
+pub struct PullProgressEvent {
+    pub model: String,
+    pub status: String,
+    pub digest: Option<String>,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+    pub percent: Option<f64>,
+    pub bytes_per_second: Option<f64>,
+    pub eta_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadState {
+    Queued,
+    Downloading,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl DownloadState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadState::Queued => "queued",
+            DownloadState::Downloading => "downloading",
+            DownloadState::Paused => "paused",
+            DownloadState::Completed => "completed",
+            DownloadState::Failed => "failed",
+            DownloadState::Cancelled => "cancelled",
+        }
+    }
+    
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "downloading" => DownloadState::Downloading,
+            "paused" => DownloadState::Paused,
+            "completed" => DownloadState::Completed,
+            "failed" => DownloadState::Failed,
+            "cancelled" => DownloadState::Cancelled,
+            _ => DownloadState::Queued,
+        }
+    }
+    
+    pub fn is_finished(&self) -> bool {
+        matches!(self, DownloadState::Completed | DownloadState::Failed | DownloadState::Cancelled)
+    }
+}
+
+/// A model pull tracked by the download manager, emitted as
+/// `download-updated` whenever its state or progress changes. `completed`
+/// and `total` add up every layer seen so far.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Download {
+    pub id: i64,
+    pub model: String,
+    pub provider_id: Option<i64>,
+    pub api_url: Option<String>,
+    pub state: DownloadState,
+    pub status: Option<String>,
+    pub completed: i64,
+    pub total: i64,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Progress of `create_ollama_model`, emitted as `ollama-create-progress`.
+/// Byte counts are only present while local model files are uploaded.
+#[derive(Debug, Serialize, Clone)]
+pub struct CreateProgressEvent {
+    pub model: String,
+    pub status: String,
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+    pub percent: Option<f64>,
+}
@@ -0,0 +1,455 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const KEYRING_SERVICE: &str = "com.llmpad.app";
+
+pub const SETTINGS_API_KEY: &str = "settings:api_key";
+
+pub fn provider_api_key(provider_id: i64) -> String {
+    format!("provider:{}", provider_id)
+}
+
+/// Storage for API keys and other credentials that must not live in SQLite.
+pub trait SecretStore: Send + Sync {
+    fn get(&self, name: &str) -> Result<Option<String>, String>;
+    fn set(&self, name: &str, value: &str) -> Result<(), String>;
+    fn delete(&self, name: &str) -> Result<(), String>;
+}
+
+/// Keeps secrets in memory, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    secrets: Mutex<HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl SecretStore for MemoryStore {
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(self.secrets.lock().map_err(|e| e.to_string())?.get(name).cloned())
+    }
+    
+    fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        self.secrets.lock().map_err(|e| e.to_string())?.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+    
+    fn delete(&self, name: &str) -> Result<(), String> {
+        self.secrets.lock().map_err(|e| e.to_string())?.remove(name);
+        Ok(())
+    }
+}
+
+/// Opens the OS keyring when it is usable and falls back to an encrypted
+/// file in the app data directory otherwise (e.g. headless Linux). The
+/// fallback only obfuscates; see `EncryptedFileStore`.
+pub fn open(app_dir: &Path) -> Box<dyn SecretStore> {
+    let keyring = KeyringStore::new(KEYRING_SERVICE);
+    
+    if keyring.is_available() {
+        Box::new(keyring)
+    } else {
+        Box::new(EncryptedFileStore::new(app_dir))
+    }
+}
+
+pub struct KeyringStore {
+    service: String,
+}
+
+impl KeyringStore {
+    pub fn new(service: &str) -> Self {
+        KeyringStore {
+            service: service.to_string(),
+        }
+    }
+    
+    fn entry(&self, name: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(&self.service, name).map_err(|e| e.to_string())
+    }
+    
+    fn is_available(&self) -> bool {
+        let probe = "llmpad:probe";
+        self.set(probe, "ok").is_ok()
+            && matches!(self.get(probe), Ok(Some(value)) if value == "ok")
+            && self.delete(probe).is_ok()
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        match self.entry(name)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Erro ao ler segredo do keyring: {}", e)),
+        }
+    }
+    
+    fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        self.entry(name)?
+            .set_password(value)
+            .map_err(|e| format!("Erro ao gravar segredo no keyring: {}", e))
+    }
+    
+    fn delete(&self, name: &str) -> Result<(), String> {
+        match self.entry(name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Erro ao remover segredo do keyring: {}", e)),
+        }
+    }
+}
+
+/// AES-256-GCM encrypted JSON map of secrets. The key is stored in an
+/// owner-only file right next to it, so this is obfuscation, not protection:
+/// it keeps keys out of `llmpad.db` and out of plain sight, but anyone who can
+/// read the app data directory can decrypt them.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: &Path) -> Self {
+        EncryptedFileStore {
+            path: dir.join("secrets.json"),
+            key_path: dir.join("secrets.key"),
+            lock: Mutex::new(()),
+        }
+    }
+    
+    fn cipher(&self) -> Result<Aes256Gcm, String> {
+        let key = match fs::read(&self.key_path) {
+            Ok(bytes) if bytes.len() == 32 => bytes,
+            Ok(_) => return Err("Arquivo de chave de segredos inválido".to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = Aes256Gcm::generate_key(OsRng).to_vec();
+                write_private(&self.key_path, &key)?;
+                key
+            }
+            Err(e) => return Err(format!("Erro ao ler chave de segredos: {}", e)),
+        };
+        
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+    
+    fn load(&self) -> Result<HashMap<String, String>, String> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Erro ao ler arquivo de segredos: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(format!("Erro ao ler arquivo de segredos: {}", e)),
+        }
+    }
+    
+    fn save(&self, secrets: &HashMap<String, String>) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(secrets).map_err(|e| e.to_string())?;
+        write_private(&self.path, content.as_bytes())
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+        
+        let Some(encoded) = self.load()?.remove(name) else {
+            return Ok(None);
+        };
+        
+        let data = BASE64
+            .decode(encoded)
+            .map_err(|e| format!("Segredo corrompido: {}", e))?;
+        
+        if data.len() < 12 {
+            return Err("Segredo corrompido".to_string());
+        }
+        
+        let (nonce, ciphertext) = data.split_at(12);
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Não foi possível descriptografar o segredo".to_string())?;
+        
+        String::from_utf8(plaintext).map(Some).map_err(|e| e.to_string())
+    }
+    
+    fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+        
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| "Não foi possível criptografar o segredo".to_string())?;
+        
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        
+        let mut secrets = self.load()?;
+        secrets.insert(name.to_string(), BASE64.encode(data));
+        self.save(&secrets)
+    }
+    
+    fn delete(&self, name: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+        
+        let mut secrets = self.load()?;
+        if secrets.remove(name).is_some() {
+            self.save(&secrets)?;
+        }
+        
+        Ok(())
+    }
+}
+
+fn write_private(path: &Path, content: &[u8]) -> Result<(), String> {
+    let tmp_path = path.with_extension("tmp");
+    
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    
+    {
+        use std::io::Write;
+        let mut file = options
+            .open(&tmp_path)
+            .map_err(|e| format!("Erro ao gravar {}: {}", tmp_path.display(), e))?;
+        file.write_all(content)
+            .map_err(|e| format!("Erro ao gravar {}: {}", tmp_path.display(), e))?;
+    }
+    
+    fs::rename(&tmp_path, path).map_err(|e| format!("Erro ao gravar {}: {}", path.display(), e))
+}
+
+/// Stores a secret and returns its reference, or removes it when `value`
+/// is empty.
+pub fn store_secret(store: &dyn SecretStore, name: &str, value: &str) -> Result<Option<String>, String> {
+    if value.is_empty() {
+        store.delete(name)?;
+        Ok(None)
+    } else {
+        store.set(name, value)?;
+        Ok(Some(name.to_string()))
+    }
+}
+
+/// Reads the key behind `reference`, or `plaintext` for a row whose key was
+/// never moved to the secret store. A reference to a secret that is gone is
+/// an error, so requests do not silently go out without a key.
+pub fn read_secret(store: &dyn SecretStore, reference: Option<&str>, plaintext: &str) -> Result<String, String> {
+    match reference {
+        Some(name) => store.get(name)?.ok_or_else(|| {
+            format!(
+                "A chave de API ({}) não foi encontrada no armazenamento de segredos; informe-a novamente nas configurações",
+                name
+            )
+        }),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+/// Moves API keys still stored in plaintext columns into the secret store,
+/// leaving only a reference behind. The old values are overwritten on disk
+/// too, not just unlinked into free pages.
+pub fn migrate_plaintext_keys(conn: &Connection, store: &dyn SecretStore) -> Result<(), String> {
+    conn.pragma_update(None, "secure_delete", true)
+        .map_err(|e| e.to_string())?;
+    
+    let mut stmt = conn
+        .prepare("SELECT id, api_key FROM providers WHERE api_key <> ''")
+        .map_err(|e| e.to_string())?;
+    
+    let providers: Vec<(i64, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    let mut moved = !providers.is_empty();
+    for (id, api_key) in providers {
+        let reference = store_secret(store, &provider_api_key(id), &api_key)?;
+        conn.execute(
+            "UPDATE providers SET api_key = '', api_key_ref = ?1 WHERE id = ?2",
+            rusqlite::params![reference, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    let settings_key: String = conn
+        .query_row("SELECT api_key FROM settings WHERE id = 1", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    
+    if !settings_key.is_empty() {
+        let reference = store_secret(store, SETTINGS_API_KEY, &settings_key)?;
+        conn.execute(
+            "UPDATE settings SET api_key = '', api_key_ref = ?1 WHERE id = 1",
+            [reference],
+        )
+        .map_err(|e| e.to_string())?;
+        moved = true;
+    }
+    
+    if moved {
+        conn.execute_batch("VACUUM").map_err(|e| e.to_string())?;
+    }
+    
+    Ok(())
+}
+
+/// Blanks the plaintext key columns in the backups taken before keys moved to
+/// the secret store. Only call this once `migrate_plaintext_keys` succeeded;
+/// a restored backup then needs its keys entered again.
+pub fn scrub_backups(db_path: &Path) -> Result<(), String> {
+    for backup_path in crate::migrations::backups(db_path)? {
+        let conn = Connection::open(&backup_path)
+            .map_err(|e| format!("Erro ao abrir backup {}: {}", backup_path.display(), e))?;
+        conn.pragma_update(None, "secure_delete", true)
+            .map_err(|e| e.to_string())?;
+        
+        let mut blanked = 0;
+        for table in ["settings", "providers"] {
+            if has_column(&conn, table, "api_key")? {
+                blanked += conn
+                    .execute(&format!("UPDATE {} SET api_key = '' WHERE api_key <> ''", table), [])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        
+        if blanked > 0 {
+            conn.execute_batch("VACUUM").map_err(|e| e.to_string())?;
+        }
+    }
+    
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        [table, column],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("llmpad-secrets-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    #[test]
+    fn encrypted_file_store_round_trips() {
+        let dir = temp_dir("round-trip");
+        let store = EncryptedFileStore::new(&dir);
+        
+        assert_eq!(store.get("provider:1").unwrap(), None);
+        store.set("provider:1", "sk-çhave-🔑").unwrap();
+        store.set("provider:2", "outra").unwrap();
+        
+        let content = fs::read_to_string(dir.join("secrets.json")).unwrap();
+        assert!(!content.contains("sk-"));
+        
+        let reopened = EncryptedFileStore::new(&dir);
+        assert_eq!(reopened.get("provider:1").unwrap().as_deref(), Some("sk-çhave-🔑"));
+        
+        reopened.delete("provider:1").unwrap();
+        reopened.delete("provider:1").unwrap();
+        assert_eq!(reopened.get("provider:1").unwrap(), None);
+        assert_eq!(reopened.get("provider:2").unwrap().as_deref(), Some("outra"));
+    }
+    
+    #[test]
+    fn encrypted_file_store_rejects_tampered_secrets() {
+        let dir = temp_dir("tampered");
+        let store = EncryptedFileStore::new(&dir);
+        store.set("provider:1", "sk-secret").unwrap();
+        
+        let mut secrets: HashMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(dir.join("secrets.json")).unwrap()).unwrap();
+        let mut data = BASE64.decode(&secrets["provider:1"]).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        secrets.insert("provider:1".to_string(), BASE64.encode(&data));
+        secrets.insert("short".to_string(), BASE64.encode([0u8; 4]));
+        secrets.insert("not-base64".to_string(), "***".to_string());
+        fs::write(dir.join("secrets.json"), serde_json::to_string(&secrets).unwrap()).unwrap();
+        
+        assert!(store.get("provider:1").is_err());
+        assert!(store.get("short").is_err());
+        assert!(store.get("not-base64").is_err());
+    }
+    
+    #[test]
+    fn encrypted_file_store_rejects_a_different_key() {
+        let dir = temp_dir("wrong-key");
+        let store = EncryptedFileStore::new(&dir);
+        store.set("provider:1", "sk-secret").unwrap();
+        
+        fs::write(dir.join("secrets.key"), [7u8; 32]).unwrap();
+        assert!(store.get("provider:1").is_err());
+        
+        fs::write(dir.join("secrets.key"), [7u8; 5]).unwrap();
+        assert!(store.get("provider:1").is_err());
+    }
+    
+    #[test]
+    fn secrets_are_read_by_reference_or_from_legacy_plaintext() {
+        let store = MemoryStore::default();
+        
+        let reference = store_secret(&store, "provider:1", "sk-stored").unwrap();
+        assert_eq!(reference.as_deref(), Some("provider:1"));
+        assert_eq!(read_secret(&store, reference.as_deref(), "").unwrap(), "sk-stored");
+        assert_eq!(read_secret(&store, reference.as_deref(), "sk-stale").unwrap(), "sk-stored");
+        assert_eq!(read_secret(&store, None, "sk-legacy").unwrap(), "sk-legacy");
+        
+        assert_eq!(store_secret(&store, "provider:1", "").unwrap(), None);
+        assert!(read_secret(&store, Some("provider:1"), "").is_err());
+    }
+    
+    #[test]
+    fn plaintext_keys_move_to_the_store() {
+        let db = crate::database::Database::in_memory();
+        let conn = db.conn.lock().unwrap();
+        conn.execute("UPDATE settings SET api_key = 'sk-settings' WHERE id = 1", []).unwrap();
+        conn.execute("UPDATE providers SET api_key = 'sk-provider' WHERE id = 1", []).unwrap();
+        
+        let store = MemoryStore::default();
+        migrate_plaintext_keys(&conn, &store).unwrap();
+        
+        let settings: (String, Option<String>) = conn
+            .query_row("SELECT api_key, api_key_ref FROM settings WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        let provider: (String, Option<String>) = conn
+            .query_row("SELECT api_key, api_key_ref FROM providers WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        
+        assert_eq!(settings, (String::new(), Some(SETTINGS_API_KEY.to_string())));
+        assert_eq!(provider, (String::new(), Some(provider_api_key(1))));
+        assert_eq!(store.get(SETTINGS_API_KEY).unwrap().as_deref(), Some("sk-settings"));
+        assert_eq!(store.get(&provider_api_key(1)).unwrap().as_deref(), Some("sk-provider"));
+        
+        migrate_plaintext_keys(&conn, &store).unwrap();
+        assert_eq!(store.get(&provider_api_key(1)).unwrap().as_deref(), Some("sk-provider"));
+    }
+}
@@ -0,0 +1,355 @@
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::oneshot;
+use crate::models::{FunctionDefinition, ToolDefinition};
+
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>>;
+
+/// A function the model can call. `parameters` is the JSON schema of the
+/// arguments object; `call` receives the parsed arguments and returns the
+/// text sent back to the model. Tools that reach outside the app can ask for
+/// the user's approval before each call.
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn parameters(&self) -> Value;
+    fn call(&self, arguments: Value) -> ToolFuture<'_>;
+    
+    fn requires_approval(&self) -> bool {
+        false
+    }
+}
+
+/// Whether tool calling is switched on in the settings.
+pub fn enabled(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT tools_enabled FROM settings WHERE id = 1", [], |row| row.get(0))
+}
+
+/// Tools available to conversations, keyed by name.
+pub struct ToolRegistry {
+    tools: RwLock<BTreeMap<String, Arc<dyn Tool>>>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        let registry = ToolRegistry {
+            tools: RwLock::new(BTreeMap::new()),
+        };
+        registry.register(Arc::new(CurrentDateTime));
+        registry.register(Arc::new(Calculator));
+        registry
+    }
+}
+
+impl ToolRegistry {
+    pub fn register(&self, tool: Arc<dyn Tool>) {
+        if let Ok(mut tools) = self.tools.write() {
+            tools.insert(tool.name().to_string(), tool);
+        }
+    }
+    
+    /// Removes every tool whose name starts with `prefix`, e.g. the tools of
+    /// an MCP server that went away.
+    pub fn unregister_prefix(&self, prefix: &str) {
+        if let Ok(mut tools) = self.tools.write() {
+            tools.retain(|name, _| !name.starts_with(prefix));
+        }
+    }
+    
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.read().ok()?.get(name).cloned()
+    }
+    
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        let Ok(tools) = self.tools.read() else {
+            return vec![];
+        };
+        
+        tools
+            .values()
+            .map(|tool| ToolDefinition {
+                kind: "function".to_string(),
+                function: FunctionDefinition {
+                    name: tool.name().to_string(),
+                    description: tool.description().to_string(),
+                    parameters: tool.parameters(),
+                },
+            })
+            .collect()
+    }
+    
+    /// Runs a tool call as returned by the model, with its JSON-encoded
+    /// arguments.
+    pub async fn call(&self, name: &str, arguments: &str) -> Result<String, String> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| format!("Ferramenta desconhecida: {}", name))?;
+        
+        let arguments = if arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(arguments).map_err(|e| format!("Argumentos inválidos: {}", e))?
+        };
+        
+        tool.call(arguments).await
+    }
+}
+
+/// Tool calls waiting for the user to allow or deny them.
+#[derive(Default)]
+pub struct ToolApprovals {
+    pending: Mutex<HashMap<u64, oneshot::Sender<bool>>>,
+    next_id: AtomicU64,
+}
+
+impl ToolApprovals {
+    pub fn request(&self) -> Result<ApprovalGuard<'_>, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().map_err(|e| e.to_string())?.insert(id, tx);
+        
+        Ok(ApprovalGuard {
+            approvals: self,
+            id,
+            response: rx,
+        })
+    }
+    
+    /// Answers a pending request. Returns false when it is no longer waiting.
+    pub fn respond(&self, id: u64, approved: bool) -> Result<bool, String> {
+        let mut pending = self.pending.lock().map_err(|e| e.to_string())?;
+        
+        match pending.remove(&id) {
+            Some(tx) => Ok(tx.send(approved).is_ok()),
+            None => Ok(false),
+        }
+    }
+}
+
+pub struct ApprovalGuard<'a> {
+    approvals: &'a ToolApprovals,
+    pub id: u64,
+    pub response: oneshot::Receiver<bool>,
+}
+
+impl Drop for ApprovalGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.approvals.pending.lock() {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+struct CurrentDateTime;
+
+impl Tool for CurrentDateTime {
+    fn name(&self) -> &str {
+        "current_datetime"
+    }
+    
+    fn description(&self) -> &str {
+        "Returns the current local date, time and weekday."
+    }
+    
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+    
+    fn call(&self, _arguments: Value) -> ToolFuture<'_> {
+        Box::pin(async {
+            Ok(chrono::Local::now().format("%Y-%m-%d %H:%M:%S (%A, UTC%:z)").to_string())
+        })
+    }
+}
+
+struct Calculator;
+
+impl Tool for Calculator {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+    
+    fn description(&self) -> &str {
+        "Evaluates an arithmetic expression with + - * / ^, parentheses and decimal numbers."
+    }
+    
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "expression": { "type": "string", "description": "Expression to evaluate, e.g. (2 + 3) * 4.5" }
+            },
+            "required": ["expression"]
+        })
+    }
+    
+    fn call(&self, arguments: Value) -> ToolFuture<'_> {
+        Box::pin(async move {
+            let expression = arguments
+                .get("expression")
+                .and_then(Value::as_str)
+                .ok_or_else(|| "Argumento 'expression' ausente".to_string())?;
+            
+            Ok(evaluate(expression)?.to_string())
+        })
+    }
+}
+
+fn evaluate(expression: &str) -> Result<f64, String> {
+    let mut parser = Expression {
+        chars: expression.chars().filter(|c| !c.is_whitespace()).collect(),
+        position: 0,
+    };
+    let value = parser.sum()?;
+    if parser.position < parser.chars.len() {
+        return Err(format!("Caractere inesperado: {}", parser.chars[parser.position]));
+    }
+    if !value.is_finite() {
+        return Err("Resultado indefinido".to_string());
+    }
+    
+    Ok(value)
+}
+
+/// Recursive-descent parser for the calculator tool.
+struct Expression {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Expression {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+    
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            let rhs = self.product()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+    
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.power()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            let rhs = self.power()?;
+            value = if op == '*' { value * rhs } else { value / rhs };
+        }
+        Ok(value)
+    }
+    
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.unary()?;
+        if self.peek() == Some('^') {
+            self.position += 1;
+            let exponent = self.power()?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+    
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some('-') => {
+                self.position += 1;
+                Ok(-self.power()?)
+            }
+            Some('+') => {
+                self.position += 1;
+                self.unary()
+            }
+            Some('(') => {
+                self.position += 1;
+                let value = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err("Parêntese não fechado".to_string());
+                }
+                self.position += 1;
+                Ok(value)
+            }
+            _ => self.number(),
+        }
+    }
+    
+    fn number(&mut self) -> Result<f64, String> {
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.' || c == ',') {
+            self.position += 1;
+        }
+        
+        let literal: String = self.chars[start..self.position].iter().collect();
+        literal
+            .replace(',', ".")
+            .parse()
+            .map_err(|_| match self.peek() {
+                Some(c) if literal.is_empty() => format!("Caractere inesperado: {}", c),
+                _ => format!("Número inválido: {}", literal),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn calculator_follows_operator_precedence() {
+        assert_eq!(evaluate("2 + 3 * 4"), Ok(14.0));
+        assert_eq!(evaluate("(2 + 3) * 4.5"), Ok(22.5));
+        assert_eq!(evaluate("10 - 4 - 3"), Ok(3.0));
+        assert_eq!(evaluate("8 / 4 / 2"), Ok(1.0));
+    }
+    
+    #[test]
+    fn calculator_powers_are_right_associative_and_bind_tighter_than_minus() {
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(evaluate("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(evaluate("2 ^ -1"), Ok(0.5));
+        assert_eq!(evaluate("+3"), Ok(3.0));
+    }
+    
+    #[test]
+    fn calculator_accepts_decimal_commas() {
+        assert_eq!(evaluate("1,5 * 2"), Ok(3.0));
+    }
+    
+    #[test]
+    fn calculator_rejects_malformed_expressions() {
+        assert_eq!(evaluate("(1 + 2"), Err("Parêntese não fechado".to_string()));
+        assert_eq!(evaluate("1 + 2)"), Err("Caractere inesperado: )".to_string()));
+        assert_eq!(evaluate("2 * x"), Err("Caractere inesperado: x".to_string()));
+        assert_eq!(evaluate("1.2.3"), Err("Número inválido: 1.2.3".to_string()));
+        assert_eq!(evaluate(""), Err("Número inválido: ".to_string()));
+    }
+    
+    #[test]
+    fn calculator_rejects_undefined_results() {
+        assert_eq!(evaluate("1 / 0"), Err("Resultado indefinido".to_string()));
+        assert_eq!(evaluate("(-1) ^ 0.5"), Err("Resultado indefinido".to_string()));
+    }
+    
+    #[tokio::test]
+    async fn registry_calls_tools_with_json_arguments() {
+        let registry = ToolRegistry::default();
+        
+        assert_eq!(registry.call("calculator", r#"{"expression": "6 * 7"}"#).await, Ok("42".to_string()));
+        assert_eq!(
+            registry.call("calculator", "").await,
+            Err("Argumento 'expression' ausente".to_string())
+        );
+        assert!(registry.call("calculator", "{").await.unwrap_err().starts_with("Argumentos inválidos"));
+        assert_eq!(
+            registry.call("missing", "{}").await,
+            Err("Ferramenta desconhecida: missing".to_string())
+        );
+    }
+}
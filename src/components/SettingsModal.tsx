@@ -43,13 +43,18 @@ export function SettingsModal() {
             </label>
             <input
               type="password"
-              value={settings.api_key}
+              value={settings.api_key ?? ""}
               onChange={(e) =>
                 setSettings({ ...settings, api_key: e.target.value })
               }
-              placeholder="sk-..."
+              placeholder={settings.has_api_key ? "••••••••" : "sk-..."}
               className="w-full dark:bg-gray-700 bg-gray-300 border dark:border-gray-600 border-gray-300 rounded-lg px-3 py-2 focus:outline-none focus:border-blue-500"
             />
+            {settings.secrets_error && (
+              <p className="text-xs text-red-400 mt-1">
+                {settings.secrets_error}
+              </p>
+            )}
           </div>
           <div>
             <label className="text-sm dark:text-gray-400 text-gray-600 mb-1 flex items-center justify-between">
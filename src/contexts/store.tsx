@@ -6,7 +6,16 @@ import {
   useEffect,
   useRef,
 } from "react";
-import { IMessage, IConversation, IAppSettings, IModelFile } from "../types";
+import {
+  IMessage,
+  IConversation,
+  IAppSettings,
+  IContextReport,
+  IConversationTitleEvent,
+  IModelFile,
+  IPullProgressEvent,
+  ICreateModelError,
+} from "../types";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 
@@ -58,7 +67,6 @@ export const AppProvider = ({ children }: { children: ReactNode }) => {
   const [showSettings, setShowSettings] = useState(false);
   const [settings, setSettings] = useState<IAppSettings>({
     api_url: "http://localhost:11434/v1",
-    api_key: "",
     model: "llama3.2",
   });
   const [modelFiles, setModelFiles] = useState<IModelFile[]>([]);
@@ -80,6 +88,36 @@ export const AppProvider = ({ children }: { children: ReactNode }) => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
   }, [messages]);
 
+  useEffect(() => {
+    let disposed = false;
+    let unlisten: null | (() => void) = null;
+
+    (async () => {
+      const unsub = await listen<IConversationTitleEvent>(
+        "conversation-title-updated",
+        (event) => {
+          const { conversation_id, title } = event.payload;
+          setConversations((prev) =>
+            prev.map((c) => (c.id === conversation_id ? { ...c, title } : c)),
+          );
+          setCurrentConversation((prev) =>
+            prev && prev.id === conversation_id ? { ...prev, title } : prev,
+          );
+        },
+      );
+      if (disposed) {
+        unsub();
+        return;
+      }
+      unlisten = unsub;
+    })();
+
+    return () => {
+      disposed = true;
+      if (unlisten) unlisten();
+    };
+  }, []);
+
   useEffect(() => {
     const root = window.document.documentElement;
     if (theme === "dark") {
@@ -95,33 +133,38 @@ export const AppProvider = ({ children }: { children: ReactNode }) => {
     let disposed = false;
     let unlisten: null | (() => void) = null;
 
-    const parseSizeToBytes = (value: number, unit: string) => {
-      const u = unit.toUpperCase();
-      if (u === "KB") return value * 1024;
-      if (u === "MB") return value * 1024 * 1024;
-      if (u === "GB") return value * 1024 * 1024 * 1024;
-      return value;
+    const formatBytes = (bytes: number) => {
+      if (bytes >= 1024 ** 3) return `${(bytes / 1024 ** 3).toFixed(1)} GB`;
+      if (bytes >= 1024 ** 2) return `${(bytes / 1024 ** 2).toFixed(1)} MB`;
+      return `${(bytes / 1024).toFixed(0)} KB`;
     };
 
-    const tryParsePercent = (line: string) => {
-      const m = line.match(
-        /(\d+(?:\.\d+)?)\s*(KB|MB|GB)\s*\/\s*(\d+(?:\.\d+)?)\s*(KB|MB|GB)/i,
-      );
-      if (!m) return null;
-      const downloaded = parseSizeToBytes(parseFloat(m[1]), m[2]);
-      const total = parseSizeToBytes(parseFloat(m[3]), m[4]);
-      if (!Number.isFinite(downloaded) || !Number.isFinite(total) || total <= 0)
-        return null;
-      return Math.min(100, Math.max(0, (downloaded / total) * 100));
+    const formatEta = (seconds: number) => {
+      if (seconds >= 3600)
+        return `${Math.floor(seconds / 3600)}h ${Math.floor((seconds % 3600) / 60)}min`;
+      if (seconds >= 60) return `${Math.floor(seconds / 60)}min ${seconds % 60}s`;
+      return `${seconds}s`;
     };
 
     (async () => {
-      const unsub = await listen<string>("ollama-pull-progress", (event) => {
-        if (disposed) return;
-        setDownloadProgress(event.payload);
-        const p = tryParsePercent(event.payload);
-        if (p !== null) setDownloadPercent(p);
-      });
+      const unsub = await listen<IPullProgressEvent>(
+        "ollama-pull-progress",
+        (event) => {
+          if (disposed || event.payload.model !== downloadingModel) return;
+          const p = event.payload;
+          const details: string[] = [];
+          if (p.completed != null && p.total != null)
+            details.push(`${formatBytes(p.completed)} / ${formatBytes(p.total)}`);
+          if (p.bytes_per_second != null)
+            details.push(`${formatBytes(p.bytes_per_second)}/s`);
+          if (p.eta_seconds != null)
+            details.push(`${formatEta(p.eta_seconds)} restantes`);
+          setDownloadProgress(
+            details.length ? `${p.status} — ${details.join(" · ")}` : p.status,
+          );
+          setDownloadPercent(p.percent);
+        },
+      );
       if (disposed) {
         unsub();
         return;
@@ -194,7 +237,8 @@ export const AppProvider = ({ children }: { children: ReactNode }) => {
       );
       loadOllamaModels();
     } catch (e) {
-      alert(`Erro ao criar modelo: ${e}`);
+      const error = e as ICreateModelError;
+      alert(error.message ?? `Erro ao criar modelo: ${e}`);
     }
   };
 
@@ -203,7 +247,12 @@ export const AppProvider = ({ children }: { children: ReactNode }) => {
     setDownloadProgress("Iniciando download...");
     setDownloadPercent(null);
     try {
-      alert(await invoke<string>("pull_ollama_model", { modelName: name }));
+      alert(
+        await invoke<string>("pull_ollama_model", {
+          modelName: name,
+          apiUrl: settings.api_url,
+        }),
+      );
       await loadOllamaModels();
     } catch (e) {
       alert(`Erro ao baixar modelo: ${e}`);
@@ -282,7 +331,7 @@ export const AppProvider = ({ children }: { children: ReactNode }) => {
 
     try {
       const [conv, userMsg, assistantMsg] = await invoke<
-        [IConversation, IMessage, IMessage]
+        [IConversation, IMessage, IMessage, IContextReport]
       >("send_message_complete", {
         conversationId: currentConversation?.id || null,
         userInput,
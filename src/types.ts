@@ -4,6 +4,27 @@ export interface IMessage {
   role: "user" | "assistant";
   content: string;
   created_at: string;
+  stopped?: boolean;
+  model?: string | null;
+  parent_message_id?: number | null;
+}
+
+export interface IAttachment {
+  id: number;
+  message_id: number | null;
+  kind: "image" | "document";
+  file_name: string;
+  mime_type: string;
+  size: number;
+  created_at: string;
+}
+
+export interface IGenerationParams {
+  temperature?: number;
+  top_p?: number;
+  max_tokens?: number;
+  stop?: string[];
+  seed?: number;
 }
 
 export interface IConversation {
@@ -11,12 +32,148 @@ export interface IConversation {
   title: string;
   created_at: string;
   updated_at: string;
+  generation_params?: IGenerationParams | null;
+  system_prompt?: string | null;
+  model?: string | null;
+  api_url?: string | null;
+  provider_id?: number | null;
+  current_message_id?: number | null;
 }
 
 export interface IAppSettings {
   api_url: string;
-  api_key: string;
+  api_key?: string;
+  has_api_key?: boolean;
   model: string;
+  generation_params?: IGenerationParams;
+  system_prompt?: string;
+  default_provider_id?: number | null;
+  context_length?: number;
+  context_limits?: Record<string, number>;
+  context_strategy?: "truncate" | "summarize";
+  auto_title?: boolean;
+  tools_enabled?: boolean;
+  max_concurrent_downloads?: number;
+  secrets_error?: string | null;
+}
+
+export interface IConversationTitleEvent {
+  conversation_id: number;
+  title: string;
+}
+
+export interface IToolCallEvent {
+  conversation_id: number;
+  name: string;
+  arguments: string;
+  result: string;
+  is_error: boolean;
+}
+
+export interface IToolApprovalRequest {
+  id: number;
+  conversation_id: number;
+  name: string;
+  arguments: string;
+}
+
+export type McpTransport = "stdio" | "http";
+
+export interface IMcpServerInput {
+  name: string;
+  transport: McpTransport;
+  command?: string | null;
+  args?: string[];
+  env?: Record<string, string>;
+  url?: string | null;
+  enabled?: boolean;
+  require_approval?: boolean;
+}
+
+export interface IMcpServer {
+  id: number;
+  name: string;
+  transport: McpTransport;
+  command: string | null;
+  args: string[];
+  env: Record<string, string>;
+  url: string | null;
+  enabled: boolean;
+  require_approval: boolean;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface IMcpServerStatus {
+  server_id: number;
+  state: "stopped" | "starting" | "running" | "failed";
+  tools: string[];
+  error: string | null;
+  restarts: number;
+}
+
+export interface IContextReport {
+  context_length: number;
+  estimated_tokens: number;
+  dropped_message_ids: number[];
+  summarized: boolean;
+  truncated_attachment_ids: number[];
+  sources: ICitation[];
+}
+
+export interface ICitation {
+  index: number;
+  knowledge_base_id: number;
+  chunk_id: number;
+  source: string;
+  score: number;
+}
+
+export interface IKnowledgeBase {
+  id: number;
+  name: string;
+  source_path: string;
+  embedding_model: string;
+  provider_id: number | null;
+  api_url: string | null;
+  chunk_count: number;
+  indexed_at: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface IIngestReport {
+  knowledge_base_id: number;
+  files: number;
+  chunks: number;
+  skipped_files: string[];
+}
+
+export interface IIngestProgressEvent {
+  knowledge_base_id: number;
+  processed_files: number;
+  total_files: number;
+  chunks: number;
+}
+
+export interface IProvider {
+  id: number;
+  name: string;
+  kind: string;
+  api_url: string;
+  has_api_key: boolean;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface ISearchHit {
+  conversation_id: number;
+  conversation_title: string;
+  message_id: number | null;
+  role: "user" | "assistant" | null;
+  snippet: string;
+  rank: number;
+  created_at: string;
 }
 
 export interface IModelFile {
@@ -24,3 +181,82 @@ export interface IModelFile {
   path: string;
   content: string;
 }
+
+export interface IModelParameter {
+  name: string;
+  value: number | boolean | string;
+}
+
+export interface IModelfileDiagnostic {
+  line: number;
+  column: number;
+  severity: "error" | "warning";
+  message: string;
+}
+
+export interface IModelfileFields {
+  base_model: string;
+  parameters?: IModelParameter[];
+  system?: string | null;
+  template?: string | null;
+}
+
+export interface IModelFileInfo extends IModelFile {
+  base_model: string | null;
+  is_base_available: boolean;
+  parameters: IModelParameter[];
+  diagnostics: IModelfileDiagnostic[];
+}
+
+export interface IPullProgressEvent {
+  model: string;
+  status: string;
+  digest: string | null;
+  completed: number | null;
+  total: number | null;
+  percent: number | null;
+  bytes_per_second: number | null;
+  eta_seconds: number | null;
+}
+
+export type DownloadState =
+  | "queued"
+  | "downloading"
+  | "paused"
+  | "completed"
+  | "failed"
+  | "cancelled";
+
+export interface IDownload {
+  id: number;
+  model: string;
+  provider_id: number | null;
+  api_url: string | null;
+  state: DownloadState;
+  status: string | null;
+  completed: number;
+  total: number;
+  error: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface ICreateProgressEvent {
+  model: string;
+  status: string;
+  completed: number | null;
+  total: number | null;
+  percent: number | null;
+}
+
+export interface ICreateModelError {
+  kind:
+    | "invalid_name"
+    | "invalid_modelfile"
+    | "file"
+    | "connection"
+    | "ollama"
+    | "failed"
+    | "interrupted";
+  message: string;
+}